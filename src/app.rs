@@ -1,30 +1,161 @@
+use bindings::Bindings;
 use color;
+use config::Config;
 use fps_counter::FPSCounter;
+use gamepad::Gamepads;
 use glutin_window::GlutinWindow;
 use graphics;
 use opengl_graphics::{OpenGL, GlGraphics};
 use piston::event_loop::{EventLoop, Events, EventSettings};
-use piston::input::{Button, Input, Motion};
-use piston::window::{AdvancedWindow, WindowSettings};
-use state::{self, State};
+use piston::input::{Button, Input, Key};
+use piston::window::{AdvancedWindow, Window, WindowSettings};
+use raster::{self, FrameSequence};
+use recording::{Event, Header, Recorder, Replay};
+use recovery::Recovery;
+use scene::{Scene, SceneError};
+use state::{self, Restore, State, StateStack};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use toolbar::Toolbar;
+
+// Where F11 writes frames unless --frames says otherwise
+const DEFAULT_FRAMES_DIR: &'static str = "frames";
+// Loaded when it exists and --bindings isn't given
+const DEFAULT_BINDINGS_PATH: &'static str = "bindings.json";
 
 pub struct App {
     // Main window
     window: GlutinWindow,
+    // The window doesn't report them
+    gamepads: Gamepads,
+    // Updates per second
+    ups: u64,
+
+    // Game at the bottom, menus and overlays on top
+    states: StateStack,
+    toolbar: Toolbar,
+
+    // Number of updates so far, recorded events are tagged with it
+    tick: u64,
+    recorder: Option<Recorder>,
+    // While replaying, input from the window is ignored
+    replay: Option<Replay>,
 
-    current_controller: Box<State>,
+    frames_dir: PathBuf,
+    // Every rendered frame is saved while this is set
+    frames: Option<FrameSequence>,
+
+    // Marks the session as running until it exits cleanly
+    recovery: Option<Rc<Recovery>>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        // Replays don't touch the recovery files, they'd overwrite a crashed session's autosave.
+        // Neither do recordings, restoring one in the window couldn't be replayed
+        let recovery = if config.replay.is_none() && config.record.is_none() {
+            Recovery::new().map(Rc::new)
+        } else {
+            None
+        };
+
+        let mut recorder = None;
+        let mut replay = None;
+        let (mut game, header) = if let Some(ref path) = config.replay {
+            let (header, events) = Replay::open(path).unwrap_or_else(|e| {
+                eprintln!("failed to open {}: {}", path.display(), e);
+                process::exit(1);
+            });
+            replay = Some(events);
+            let mut game = game_from_header(&header);
+            game.set_replaying();
+            (game, header)
+        } else {
+            let mut game = build_game(config);
+            let header = Header {
+                seed: config.seed,
+                ups: config.ups,
+                width: config.width,
+                height: config.height,
+                history: config.history,
+                bindings: load_bindings(config),
+                scene: game.to_scene(),
+            };
+
+            match config.record {
+                Some(ref path) => {
+                    let (events, header) = Recorder::create(path, &header).unwrap_or_else(|e| {
+                        eprintln!("failed to create {}: {}", path.display(), e);
+                        process::exit(1);
+                    });
+                    recorder = Some(events);
+
+                    // Replays start from the saved scene, so the recorded session has to as well
+                    let mut game = game_from_header(&header);
+                    if let Some(ref path) = config.scene {
+                        game.set_scene_path(path.clone());
+                    }
+                    (game, header)
+                }
+                None => {
+                    game.set_bindings(header.bindings.clone());
+                    (game, header)
+                }
+            }
+        };
+
+        // Replays have to see the same scene as when they were recorded
+        if config.scene.is_some() && config.replay.is_none() {
+            game.watch_scene_file();
+        }
+
+        if let Some(ref dir) = config.prefabs {
+            game.set_prefab_dir(dir.clone());
+        }
+
+        // The player is asked in the window before a crashed session's autosave is replaced
+        let first: Box<State> = match recovery.clone() {
+            Some(recovery) => {
+                match recovery.crashed_scene() {
+                    Some(scene) => Box::new(Restore::new(game, scene, recovery)),
+                    None => {
+                        recovery.start(&mut game);
+                        Box::new(game)
+                    }
+                }
+            }
+            None => Box::new(game),
+        };
+
         App {
-            window: WindowSettings::new("", [800, 600])
-                .samples(4)
-                .vsync(true)
+            window: WindowSettings::new("", [header.width, header.height])
+                .samples(config.samples)
+                .vsync(config.vsync)
                 .build()
                 .unwrap(),
+            gamepads: Gamepads::new(),
+            ups: header.ups,
+
+            states: StateStack::new(first, header.width, header.height),
+            toolbar: Toolbar::new(header.width, header.height),
+
+            tick: 0,
+            recorder: recorder,
+            replay: replay,
 
-            current_controller: Box::new(state::Game::new()),
+            frames_dir: config.frames
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_FRAMES_DIR)),
+            frames: config.frames.as_ref().map(|dir| {
+                FrameSequence::new(dir).unwrap_or_else(|e| {
+                    eprintln!("failed to create {}: {}", dir.display(), e);
+                    process::exit(1);
+                })
+            }),
+
+            recovery: recovery,
         }
     }
 
@@ -36,51 +167,213 @@ impl App {
         let mut gl = GlGraphics::new(OpenGL::V3_2);
 
         // Main event loop
-        let mut events = Events::new(EventSettings::new().ups(60));
+        let mut events = Events::new(EventSettings::new().ups(self.ups));
         while let Some(e) = events.next(&mut self.window) {
             match e {
                 Input::Update(ref args) => {
-                    self.current_controller.update(args.dt);
+                    self.replay_events();
+                    self.poll_gamepads();
+
+                    // Clicks on the toolbar are recorded and replayed like any other input
+                    for action in self.toolbar.update(self.states.status()) {
+                        self.send(Event::Ui { action: action });
+                    }
+
+                    self.states.update(args.dt);
+                    self.tick += 1;
+                    // Recorded for the next tick, replays send them before its update
+                    self.send_loads();
                 }
 
                 Input::Render(ref args) => {
                     gl.draw(args.viewport(), |c, g| {
                         graphics::clear(color::CORNFLOWER_BLUE, g);
-                        self.current_controller.render(&c, g);
+                        self.states.render(&c, g);
+                        self.toolbar.draw(c, g);
                     });
-                    self.window.set_title(format!("fps: {}", counter.tick()));
-                }
 
-                Input::Move(Motion::MouseCursor(x, y)) => {
-                    self.current_controller.handle_mouse_move(x, y)
-                }
+                    self.save_frame();
 
-                Input::Move(Motion::MouseScroll(x, y)) => {
-                    self.current_controller.handle_mouse_scroll(x, y);
+                    let replaying = if self.replay.is_some() { " (replaying)" } else { "" };
+                    self.window.set_title(format!("fps: {}{}", counter.tick(), replaying));
                 }
 
-                Input::Press(Button::Mouse(button)) => {
-                    self.current_controller.handle_mouse_button(button, true)
-                }
+                Input::Press(Button::Keyboard(Key::F12)) => self.save_screenshot(),
+                Input::Press(Button::Keyboard(Key::F11)) => self.toggle_frames(),
 
-                Input::Release(Button::Mouse(button)) => {
-                    self.current_controller.handle_mouse_button(button, false)
-                }
+                _ => {
+                    if self.replay.is_some() {
+                        continue;
+                    }
 
-                Input::Press(Button::Keyboard(key)) => {
-                    self.current_controller.handle_key(key, true)
-                }
+                    // The toolbar gets first pick
+                    if self.toolbar.handle_input(&e) {
+                        continue;
+                    }
 
-                Input::Release(Button::Keyboard(key)) => {
-                    self.current_controller.handle_key(key, false)
+                    if let Some(event) = Event::from_input(&e) {
+                        self.send(event);
+                    }
                 }
+            }
+
+            if self.states.is_empty() {
+                break;
+            }
+        }
+
+        // Closed normally, nothing to recover next time
+        if let Some(ref recovery) = self.recovery {
+            recovery.finish();
+        }
+    }
+
+    // Read once per update, recorded like input from the window
+    fn poll_gamepads(&mut self) {
+        if self.replay.is_some() {
+            return;
+        }
+
+        for event in self.gamepads.poll() {
+            self.send(event);
+        }
+    }
 
-                Input::Resize(width, height) => {
-                    self.current_controller.handle_resize(width, height)
+    fn send(&mut self, event: Event) {
+        if let Some(ref mut recorder) = self.recorder {
+            if let Err(e) = recorder.record(self.tick, &event) {
+                eprintln!("failed to record input: {}", e);
+            }
+        }
+
+        event.send_to(&mut self.states);
+        self.send_loads();
+    }
+
+    // Files read while handling input or updating are recorded like input
+    fn send_loads(&mut self) {
+        for load in self.states.take_loads() {
+            self.send(Event::Load { load: load });
+        }
+    }
+
+    // Rasterized in software, so it looks the same as in headless runs
+    fn capture(&self) -> raster::Canvas {
+        let size = self.window.size();
+        raster::capture(&self.states,
+                        size.width,
+                        size.height,
+                        color::CORNFLOWER_BLUE)
+    }
+
+    fn save_screenshot(&self) {
+        // First name that isn't taken
+        let path = (0..)
+            .map(|i| PathBuf::from(format!("screenshot-{:03}.png", i)))
+            .find(|path| !path.exists())
+            .unwrap();
+
+        match self.capture().save(&path) {
+            Ok(()) => println!("saved screenshot to {}", path.display()),
+            Err(e) => eprintln!("failed to save {}: {}", path.display(), e),
+        }
+    }
+
+    fn toggle_frames(&mut self) {
+        if self.frames.take().is_some() {
+            println!("stopped recording frames");
+            return;
+        }
+
+        match FrameSequence::new(&self.frames_dir) {
+            Ok(frames) => {
+                println!("recording frames to {}", self.frames_dir.display());
+                self.frames = Some(frames);
+            }
+            Err(e) => eprintln!("failed to create {}: {}", self.frames_dir.display(), e),
+        }
+    }
+
+    fn save_frame(&mut self) {
+        if self.frames.is_none() {
+            return;
+        }
+
+        let canvas = self.capture();
+        let saved = self.frames.as_mut().unwrap().save(&canvas);
+        if let Err(e) = saved {
+            eprintln!("failed to save frame: {}", e);
+            self.frames = None;
+        }
+    }
+
+    // Sends the events recorded before the coming update
+    fn replay_events(&mut self) {
+        let finished = match self.replay {
+            Some(ref mut replay) => {
+                while let Some(event) = replay.next_event(self.tick) {
+                    event.send_to(&mut self.states);
                 }
+                replay.is_finished()
+            }
+            None => return,
+        };
+
+        if finished {
+            println!("replay finished after {} updates", self.tick);
+            self.replay = None;
+        }
+    }
+}
 
-                _ => {}
+pub fn build_game(config: &Config) -> state::Game {
+    let mut game = state::Game::new(config.seed);
+    game.set_timestep(1.0 / config.ups as f64);
+    game.set_history_limit(config.history);
+    game.handle_resize(config.width, config.height);
+
+    if let Some(ref path) = config.scene {
+        match Scene::load(path) {
+            Ok(scene) => game.load_scene(scene),
+            // Starts from the default scene, ctrl+s creates the file
+            Err(SceneError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                eprintln!("failed to open {}: {}", path.display(), e);
+                process::exit(1);
             }
         }
+
+        game.set_scene_path(path.clone());
     }
+
+    if let Some(paused) = config.paused {
+        game.set_paused(paused);
+    }
+
+    game
+}
+
+// The defaults when there's no bindings file, unless one was asked for
+fn load_bindings(config: &Config) -> Bindings {
+    let path = match config.bindings {
+        Some(ref path) => path.clone(),
+        None if Path::new(DEFAULT_BINDINGS_PATH).exists() => PathBuf::from(DEFAULT_BINDINGS_PATH),
+        None => return Bindings::default(),
+    };
+
+    Bindings::load(&path).unwrap_or_else(|e| {
+        eprintln!("failed to load {}: {}", path.display(), e);
+        process::exit(1);
+    })
+}
+
+// Same game as the one `header` was recorded from
+fn game_from_header(header: &Header) -> state::Game {
+    let mut game = state::Game::new(header.seed);
+    game.set_timestep(1.0 / header.ups as f64);
+    game.set_history_limit(header.history);
+    game.set_bindings(header.bindings.clone());
+    game.handle_resize(header.width, header.height);
+    game.load_scene(header.scene.clone());
+    game
 }
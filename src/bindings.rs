@@ -0,0 +1,349 @@
+use piston::input::{Key, MouseButton};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+use serde_json::{self, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+// Everything the game does in response to a key or mouse button, named as in the bindings file
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Control {
+    // Held down
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    UseTool,
+
+    ZoomIn,
+    ZoomOut,
+    Pause,
+    StepBack,
+    StepForward,
+
+    Ball,
+    Box,
+    // Placed a corner per click
+    Polygon,
+    Hinge,
+    Weld,
+    Rotate,
+    Text,
+    Erase,
+    // Drawn freehand
+    Pencil,
+    Inspect,
+
+    Delete,
+    Undo,
+    Redo,
+    Save,
+    Open,
+    Copy,
+    Cut,
+    Paste,
+    Import,
+    ExportSvg,
+    ExportSvgScene,
+
+    // Finishes the polygon being placed
+    Confirm,
+    Cancel,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Trigger {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    fn count(&self) -> usize {
+        self.ctrl as usize + self.shift as usize + self.alt as usize
+    }
+
+    // Whether everything held in `self` is also held in `other`
+    fn is_within(&self, other: &Modifiers) -> bool {
+        (!self.ctrl || other.ctrl) && (!self.shift || other.shift) && (!self.alt || other.alt)
+    }
+}
+
+// A trigger and the modifiers that have to be held with it, like ctrl+shift+Z
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Chord {
+    trigger: Trigger,
+    modifiers: Modifiers,
+}
+
+impl Chord {
+    // Modifiers come first separated by +, then a key as piston names it or a mouse button
+    // prefixed with Mouse. Case doesn't matter: "ctrl+shift+Z", "Space", "MouseLeft"
+    fn parse(text: &str) -> Option<Chord> {
+        let mut parts: Vec<String> = text.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let name = match parts.pop() {
+            Some(name) => name,
+            None => return None,
+        };
+
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            match &part[..] {
+                "ctrl" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return None,
+            }
+        }
+
+        let trigger = match trigger_from_name(&name) {
+            Some(trigger) => trigger,
+            None => return None,
+        };
+
+        Some(Chord {
+                 trigger: trigger,
+                 modifiers: modifiers,
+             })
+    }
+}
+
+// Written the way `parse` reads it back
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(held, name) in &[(self.modifiers.ctrl, "ctrl+"),
+                               (self.modifiers.shift, "shift+"),
+                               (self.modifiers.alt, "alt+")] {
+            if held {
+                write!(f, "{}", name)?;
+            }
+        }
+
+        match self.trigger {
+            Trigger::Key(key) => write!(f, "{:?}", key),
+            Trigger::Mouse(button) => write!(f, "Mouse{:?}", button),
+        }
+    }
+}
+
+fn trigger_from_name(name: &str) -> Option<Trigger> {
+    if name.starts_with("mouse") {
+        // Buttons are numbered from 1, 0 is unknown
+        return (1u32..16)
+                   .map(MouseButton::from)
+                   .find(|b| format!("{:?}", b).to_lowercase() == name["mouse".len()..])
+                   .map(Trigger::Mouse);
+    }
+
+    // Printable keys are their ASCII codes, the others are past 1 << 30
+    (0u32..0x80)
+        .chain(0x4000_0000..0x4000_0200)
+        .map(Key::from)
+        .filter(|&k| k != Key::Unknown)
+        .find(|k| format!("{:?}", k).to_lowercase() == name)
+        .map(Trigger::Key)
+}
+
+// Which controls the keys and mouse buttons are bound to
+#[derive(Clone)]
+pub struct Bindings {
+    chords: Vec<(Chord, Control)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let defaults = vec![(Control::PanUp, "Up"),
+                            (Control::PanDown, "Down"),
+                            (Control::PanLeft, "Left"),
+                            (Control::PanRight, "Right"),
+                            (Control::UseTool, "MouseLeft"),
+                            (Control::ZoomIn, "W"),
+                            (Control::ZoomOut, "S"),
+                            (Control::Pause, "Space"),
+                            (Control::StepBack, "Comma"),
+                            (Control::StepForward, "Period"),
+                            (Control::Ball, "D1"),
+                            (Control::Box, "D2"),
+                            (Control::Polygon, "D3"),
+                            (Control::Hinge, "D4"),
+                            (Control::Weld, "D5"),
+                            (Control::Rotate, "R"),
+                            (Control::Text, "T"),
+                            (Control::Erase, "E"),
+                            (Control::Pencil, "P"),
+                            (Control::Inspect, "I"),
+                            (Control::Delete, "Delete"),
+                            (Control::Undo, "ctrl+Z"),
+                            (Control::Redo, "ctrl+shift+Z"),
+                            (Control::Save, "ctrl+S"),
+                            (Control::Open, "ctrl+O"),
+                            (Control::Copy, "ctrl+C"),
+                            (Control::Cut, "ctrl+X"),
+                            (Control::Paste, "ctrl+V"),
+                            (Control::Import, "ctrl+I"),
+                            (Control::ExportSvg, "ctrl+E"),
+                            (Control::ExportSvgScene, "ctrl+shift+E"),
+                            (Control::Confirm, "Return"),
+                            (Control::Cancel, "Escape")];
+
+        Bindings {
+            chords: defaults.into_iter()
+                .map(|(control, text)| {
+                         (Chord::parse(text).expect("invalid default binding"), control)
+                     })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Chord(String),
+    // A chord and two of the controls it was bound to
+    Conflict(String, Control, Control),
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BindingsError::Io(ref e) => write!(f, "{}", e),
+            BindingsError::Json(ref e) => write!(f, "invalid bindings: {}", e),
+            BindingsError::Chord(ref text) => write!(f, "unknown key or button: {}", text),
+            BindingsError::Conflict(ref text, first, second) => {
+                write!(f,
+                       "{} is bound to both {} and {}",
+                       text,
+                       control_name(first),
+                       control_name(second))
+            }
+        }
+    }
+}
+
+impl error::Error for BindingsError {
+    fn description(&self) -> &str {
+        match *self {
+            BindingsError::Io(ref e) => e.description(),
+            BindingsError::Json(ref e) => e.description(),
+            BindingsError::Chord(_) => "unknown key or button",
+            BindingsError::Conflict(..) => "key or button bound twice",
+        }
+    }
+}
+
+impl From<io::Error> for BindingsError {
+    fn from(e: io::Error) -> Self {
+        BindingsError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BindingsError {
+    fn from(e: serde_json::Error) -> Self {
+        BindingsError::Json(e)
+    }
+}
+
+impl Bindings {
+    // A JSON object from control names to lists of chords, controls it leaves out keep their
+    // default bindings and an empty list unbinds one. A chord can only be bound to one control,
+    // including the defaults that are kept
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Bindings, BindingsError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        Bindings::with_overrides(serde_json::from_str(&contents)?)
+    }
+
+    fn with_overrides(overrides: HashMap<String, Vec<String>>) -> Result<Bindings, BindingsError> {
+        let mut bindings = Bindings::default();
+        for (name, texts) in overrides {
+            let control: Control = serde_json::from_value(Value::String(name))?;
+            bindings.chords.retain(|&(_, c)| c != control);
+            for text in texts {
+                match Chord::parse(&text) {
+                    Some(chord) => bindings.chords.push((chord, control)),
+                    None => return Err(BindingsError::Chord(text)),
+                }
+            }
+        }
+
+        for (i, &(ref chord, control)) in bindings.chords.iter().enumerate() {
+            if let Some(&(_, other)) = bindings.chords[..i].iter().find(|&&(ref c, _)| c == chord) {
+                if other != control {
+                    return Err(BindingsError::Conflict(chord.to_string(), other, control));
+                }
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    // Every control with its chords, in the format of the bindings file
+    fn to_overrides(&self) -> BTreeMap<String, Vec<String>> {
+        // Controls without chords are kept so they don't get their defaults back when read
+        let mut overrides: BTreeMap<String, Vec<String>> = Bindings::default()
+            .chords
+            .iter()
+            .map(|&(_, control)| (control_name(control), Vec::new()))
+            .collect();
+        for &(ref chord, control) in &self.chords {
+            overrides.entry(control_name(control)).or_insert_with(Vec::new).push(chord.to_string());
+        }
+
+        overrides
+    }
+
+    // The control bound to the chord with the most modifiers among those held, so ctrl+shift+Z
+    // wins over ctrl+Z and W still zooms with shift held
+    pub fn pressed(&self, trigger: Trigger, modifiers: &Modifiers) -> Option<Control> {
+        self.chords
+            .iter()
+            .filter(|&&(ref chord, _)| {
+                        chord.trigger == trigger && chord.modifiers.is_within(modifiers)
+                    })
+            .max_by_key(|&&(ref chord, _)| chord.modifiers.count())
+            .map(|&(_, control)| control)
+    }
+
+    // Everything bound to `trigger`, modifiers may have changed since it was pressed
+    pub fn released(&self, trigger: Trigger) -> Vec<Control> {
+        self.chords
+            .iter()
+            .filter(|&&(ref chord, _)| chord.trigger == trigger)
+            .map(|&(_, control)| control)
+            .collect()
+    }
+}
+
+// Saved in recordings, whose keys mean nothing without the bindings they were pressed with
+impl Serialize for Bindings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_overrides().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let overrides = HashMap::deserialize(deserializer)?;
+        Bindings::with_overrides(overrides).map_err(de::Error::custom)
+    }
+}
+
+fn control_name(control: Control) -> String {
+    match serde_json::to_value(control) {
+        Ok(Value::String(name)) => name,
+        _ => format!("{:?}", control),
+    }
+}
@@ -0,0 +1,559 @@
+use graphics::types::Color;
+use na;
+use ncollide::shape::{Ball2, Compound2, ConvexHull2, Cuboid2, Plane2, Shape2, ShapeHandle2};
+use nphysics2d::detection::joint::{Anchor, BallInSocket, Fixed, Joint};
+use nphysics2d::object::{RigidBody, RigidBodyHandle, RigidBodyState};
+use nphysics2d::world::World;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+macro_rules! try_opt {
+    ($e:expr) => (
+        match $e {
+            Some(v) => v,
+            None => return None,
+        }
+    );
+}
+
+// Stable identifier for bodies, kept in their user data since handles change when a body is
+// rebuilt
+pub type BodyId = usize;
+pub type JointId = usize;
+
+// Position of a body in `Blueprint::bodies`, files refer to bodies with it. Kept apart from
+// `BodyId` so one can't be passed where the other is expected
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BodyIndex(pub usize);
+
+// Kept in the user data of every body the game creates
+struct BodyData {
+    id: BodyId,
+    color: Color,
+}
+
+fn body_data(rb: &RigidBody<f64>) -> Option<&BodyData> {
+    rb.user_data().and_then(|data| (**data).downcast_ref::<BodyData>())
+}
+
+pub fn body_id(body: &RigidBodyHandle<f64>) -> Option<BodyId> {
+    body_data(&body.borrow()).map(|data| data.id)
+}
+
+pub fn body_color(rb: &RigidBody<f64>) -> Color {
+    body_data(rb).map_or([1.0; 4], |data| data.color)
+}
+
+// Moved only by the user, not by the solver
+pub fn is_kinematic(rb: &RigidBody<f64>) -> bool {
+    match rb.state() {
+        RigidBodyState::Kinematic => true,
+        _ => false,
+    }
+}
+
+pub fn set_body_data(rb: &mut RigidBody<f64>, id: BodyId, color: Color) {
+    rb.set_user_data(Some(Box::new(BodyData {
+                                       id: id,
+                                       color: color,
+                                   })));
+}
+
+// Joints created by the user, as opposed to the grab joint which is managed separately
+#[derive(Clone)]
+pub enum JointHandle {
+    Fixed(Rc<RefCell<Fixed<f64>>>),
+    BallInSocket(Rc<RefCell<BallInSocket<f64>>>),
+}
+
+impl JointHandle {
+    pub fn bodies(&self) -> (Option<RigidBodyHandle<f64>>, Option<RigidBodyHandle<f64>>) {
+        match *self {
+            JointHandle::Fixed(ref j) => {
+                let j = j.borrow();
+                (j.anchor1().body.clone(), j.anchor2().body.clone())
+            }
+            JointHandle::BallInSocket(ref j) => {
+                let j = j.borrow();
+                (j.anchor1().body.clone(), j.anchor2().body.clone())
+            }
+        }
+    }
+
+    pub fn is_attached_to(&self, body: &RigidBodyHandle<f64>) -> bool {
+        let (body1, body2) = self.bodies();
+        body1.map_or(false, |b| Rc::ptr_eq(&b, body)) ||
+        body2.map_or(false, |b| Rc::ptr_eq(&b, body))
+    }
+
+    // World space positions of both anchors
+    pub fn anchor_positions(&self) -> (na::Point2<f64>, na::Point2<f64>) {
+        match *self {
+            JointHandle::Fixed(ref j) => {
+                let j = j.borrow();
+                (na::Point2::from_coordinates(j.anchor1_pos().translation.vector),
+                 na::Point2::from_coordinates(j.anchor2_pos().translation.vector))
+            }
+            JointHandle::BallInSocket(ref j) => {
+                let j = j.borrow();
+                (j.anchor1_pos(), j.anchor2_pos())
+            }
+        }
+    }
+
+    pub fn remove_from(&self, world: &mut World<f64>) {
+        match *self {
+            JointHandle::Fixed(ref j) => world.remove_fixed(j),
+            JointHandle::BallInSocket(ref j) => world.remove_ball_in_socket(j),
+        }
+    }
+}
+
+// A rotation kept as the unit complex number nalgebra stores, `[cos, sin]`. As an angle it would go
+// through atan2 on every save and often come back with different last bits
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Rotation([f64; 2]);
+
+impl Rotation {
+    pub fn identity() -> Rotation {
+        Rotation([1.0, 0.0])
+    }
+
+    pub fn from_unit(rotation: &na::UnitComplex<f64>) -> Rotation {
+        let complex = rotation.complex();
+        Rotation([complex.re, complex.im])
+    }
+
+    // Not normalized again, which could change it as well
+    pub fn to_unit(&self) -> na::UnitComplex<f64> {
+        let mut complex = *na::UnitComplex::identity().complex();
+        complex.re = self.0[0];
+        complex.im = self.0[1];
+        na::Unit::new_unchecked(complex)
+    }
+
+    pub fn angle(&self) -> f64 {
+        self.0[1].atan2(self.0[0])
+    }
+}
+
+fn isometry(position: &[f64; 2], rotation: &Rotation) -> na::Isometry2<f64> {
+    na::Isometry2::from_parts(na::Translation2::new(position[0], position[1]),
+                              rotation.to_unit())
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ShapeDesc {
+    Ball { radius: f64 },
+    Cuboid { half_extents: [f64; 2] },
+    Polygon { points: Vec<[f64; 2]> },
+    Plane { normal: [f64; 2] },
+    Compound { parts: Vec<PartDesc> },
+}
+
+// A piece of a compound shape, positioned relative to the body
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PartDesc {
+    pub position: [f64; 2],
+    pub rotation: Rotation,
+    pub shape: ShapeDesc,
+}
+
+impl ShapeDesc {
+    pub fn from_shape(shape: &Shape2<f64>) -> Option<ShapeDesc> {
+        if let Some(s) = shape.as_shape::<Ball2<f64>>() {
+            Some(ShapeDesc::Ball { radius: s.radius() })
+        } else if let Some(s) = shape.as_shape::<Cuboid2<f64>>() {
+            let half_extents = s.half_extents();
+            Some(ShapeDesc::Cuboid { half_extents: [half_extents.x, half_extents.y] })
+        } else if let Some(s) = shape.as_shape::<ConvexHull2<f64>>() {
+            Some(ShapeDesc::Polygon { points: s.points().iter().map(|p| [p.x, p.y]).collect() })
+        } else if let Some(s) = shape.as_shape::<Plane2<f64>>() {
+            let normal = s.normal();
+            Some(ShapeDesc::Plane { normal: [normal.x, normal.y] })
+        } else if let Some(s) = shape.as_shape::<Compound2<f64>>() {
+            let mut parts = vec![];
+            for &(ref isometry, ref handle) in s.shapes() {
+                parts.push(PartDesc {
+                               position: [isometry.translation.vector.x,
+                                          isometry.translation.vector.y],
+                               rotation: Rotation::from_unit(&isometry.rotation),
+                               shape: try_opt!(ShapeDesc::from_shape(handle.as_ref())),
+                           });
+            }
+
+            Some(ShapeDesc::Compound { parts: parts })
+        } else {
+            None
+        }
+    }
+
+    // Shapes that can be part of a compound, planes and nested compounds can't
+    fn to_handle(&self) -> Option<ShapeHandle2<f64>> {
+        match *self {
+            ShapeDesc::Ball { radius } => Some(ShapeHandle2::new(Ball2::new(radius))),
+            ShapeDesc::Cuboid { half_extents } => {
+                let half_extents = na::Vector2::new(half_extents[0], half_extents[1]);
+                Some(ShapeHandle2::new(Cuboid2::new(half_extents)))
+            }
+            ShapeDesc::Polygon { ref points } => {
+                let points = points.iter().map(|p| na::Point2::new(p[0], p[1])).collect();
+                Some(ShapeHandle2::new(ConvexHull2::new(points)))
+            }
+            ShapeDesc::Plane { .. } |
+            ShapeDesc::Compound { .. } => None,
+        }
+    }
+}
+
+// Everything needed to rebuild a rigid body
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BodyDesc {
+    pub shape: ShapeDesc,
+    pub position: [f64; 2],
+    pub rotation: Rotation,
+    pub lin_vel: [f64; 2],
+    pub ang_vel: f64,
+    // `None` for static bodies
+    pub density: Option<f64>,
+    pub restitution: f64,
+    pub friction: f64,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    // `None` keeps the default margin of the shape
+    #[serde(default)]
+    pub margin: Option<f64>,
+    #[serde(default)]
+    pub kinematic: bool,
+}
+
+fn default_color() -> Color {
+    [1.0; 4]
+}
+
+impl BodyDesc {
+    pub fn from_body(rb: &RigidBody<f64>) -> Option<BodyDesc> {
+        let shape = match ShapeDesc::from_shape(rb.shape().as_ref()) {
+            Some(s) => s,
+            None => return None,
+        };
+
+        let position = rb.position();
+        let lin_vel = rb.lin_vel();
+
+        Some(BodyDesc {
+                 shape: shape,
+                 position: [position.translation.vector.x, position.translation.vector.y],
+                 rotation: Rotation::from_unit(&position.rotation),
+                 lin_vel: [lin_vel.x, lin_vel.y],
+                 ang_vel: rb.ang_vel().x,
+                 density: if rb.is_static() {
+                     None
+                 } else {
+                     Some(rb.density().unwrap_or(1.0))
+                 },
+                 restitution: rb.restitution(),
+                 friction: rb.friction(),
+                 color: body_color(rb),
+                 margin: Some(rb.margin()),
+                 kinematic: is_kinematic(rb),
+             })
+    }
+
+    pub fn build(&self) -> RigidBody<f64> {
+        macro_rules! new_body {
+            ($shape:expr) => (
+                match self.density {
+                    Some(density) => {
+                        RigidBody::new_dynamic($shape, density, self.restitution, self.friction)
+                    }
+                    None => RigidBody::new_static($shape, self.restitution, self.friction),
+                }
+            );
+        }
+
+        let mut rb = match self.shape {
+            ShapeDesc::Ball { radius } => new_body!(Ball2::new(radius)),
+            ShapeDesc::Cuboid { half_extents } => {
+                new_body!(Cuboid2::new(na::Vector2::new(half_extents[0], half_extents[1])))
+            }
+            ShapeDesc::Polygon { ref points } => {
+                let points = points.iter().map(|p| na::Point2::new(p[0], p[1])).collect();
+                new_body!(ConvexHull2::new(points))
+            }
+            // Planes have no volume, so they can only be static
+            ShapeDesc::Plane { normal } => {
+                RigidBody::new_static(Plane2::new(na::Vector2::new(normal[0], normal[1])),
+                                      self.restitution,
+                                      self.friction)
+            }
+            ShapeDesc::Compound { ref parts } => {
+                let shapes = parts.iter()
+                    .filter_map(|part| {
+                        let position = isometry(&part.position, &part.rotation);
+                        part.shape.to_handle().map(|handle| (position, handle))
+                    })
+                    .collect();
+                new_body!(Compound2::new(shapes))
+            }
+        };
+
+        if let Some(margin) = self.margin {
+            rb.set_margin(margin);
+        }
+        if self.kinematic && !rb.is_static() {
+            rb.set_state(RigidBodyState::Kinematic);
+        }
+
+        self.apply_motion(&mut rb);
+        rb
+    }
+
+    // Whether `other` only differs in where it is and how it moves, `apply_motion` is then enough
+    // to turn one into the other
+    pub fn same_except_motion(&self, other: &BodyDesc) -> bool {
+        let mut moved = other.clone();
+        moved.position = self.position;
+        moved.rotation = self.rotation;
+        moved.lin_vel = self.lin_vel;
+        moved.ang_vel = self.ang_vel;
+        moved == *self
+    }
+
+    // Moves `rb` where this body was, with the same velocities
+    pub fn apply_motion(&self, rb: &mut RigidBody<f64>) {
+        rb.set_transformation(isometry(&self.position, &self.rotation));
+        rb.set_lin_vel(na::Vector2::new(self.lin_vel[0], self.lin_vel[1]));
+        rb.set_ang_vel(na::Vector1::new(self.ang_vel));
+    }
+}
+
+// A joint frame relative to its body, or to the world when the body is `None`. `B` is how the
+// body is referred to, a `BodyIndex` in blueprints and a `BodyId` in the world
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FrameDesc<B> {
+    pub body: Option<B>,
+    pub position: [f64; 2],
+    pub rotation: Rotation,
+}
+
+impl<B> FrameDesc<B> {
+    pub fn from_isometry(body: Option<B>, isometry: &na::Isometry2<f64>) -> FrameDesc<B> {
+        FrameDesc {
+            body: body,
+            position: [isometry.translation.vector.x, isometry.translation.vector.y],
+            rotation: Rotation::from_unit(&isometry.rotation),
+        }
+    }
+
+    pub fn from_point(body: Option<B>, point: &na::Point2<f64>) -> FrameDesc<B> {
+        FrameDesc {
+            body: body,
+            position: [point.x, point.y],
+            rotation: Rotation::identity(),
+        }
+    }
+
+    fn to_isometry(&self) -> na::Isometry2<f64> {
+        isometry(&self.position, &self.rotation)
+    }
+
+    fn to_point(&self) -> na::Point2<f64> {
+        na::Point2::new(self.position[0], self.position[1])
+    }
+
+    fn map_body<C, F>(&self, f: F) -> FrameDesc<C>
+        where F: Fn(&B) -> C
+    {
+        FrameDesc {
+            body: self.body.as_ref().map(f),
+            position: self.position,
+            rotation: self.rotation,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum JointKind {
+    Fixed,
+    BallInSocket,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct JointDesc<B> {
+    pub kind: JointKind,
+    pub anchor1: FrameDesc<B>,
+    pub anchor2: FrameDesc<B>,
+}
+
+impl<B> JointDesc<B> {
+    // `body_ref` decides what the frames refer to, returning `None` skips the joint
+    pub fn from_joint<F>(joint: &JointHandle, body_ref: F) -> Option<JointDesc<B>>
+        where F: Fn(&RigidBodyHandle<f64>) -> Option<B>
+    {
+        let (body1, body2) = joint.bodies();
+        let ref1 = match body1 {
+            Some(ref b) => Some(try_opt!(body_ref(b))),
+            None => None,
+        };
+        let ref2 = match body2 {
+            Some(ref b) => Some(try_opt!(body_ref(b))),
+            None => None,
+        };
+
+        let desc = match *joint {
+            JointHandle::Fixed(ref j) => {
+                let j = j.borrow();
+                JointDesc {
+                    kind: JointKind::Fixed,
+                    anchor1: FrameDesc::from_isometry(ref1, &j.anchor1().position),
+                    anchor2: FrameDesc::from_isometry(ref2, &j.anchor2().position),
+                }
+            }
+            JointHandle::BallInSocket(ref j) => {
+                let j = j.borrow();
+                JointDesc {
+                    kind: JointKind::BallInSocket,
+                    anchor1: FrameDesc::from_point(ref1, &j.anchor1().position),
+                    anchor2: FrameDesc::from_point(ref2, &j.anchor2().position),
+                }
+            }
+        };
+
+        Some(desc)
+    }
+
+    // Adds the joint to `world` between the bodies its frames refer to
+    pub fn build(&self,
+                 world: &mut World<f64>,
+                 body1: Option<RigidBodyHandle<f64>>,
+                 body2: Option<RigidBodyHandle<f64>>)
+                 -> JointHandle {
+        match self.kind {
+            JointKind::Fixed => {
+                let anchor1 = Anchor::new(body1, self.anchor1.to_isometry());
+                let anchor2 = Anchor::new(body2, self.anchor2.to_isometry());
+                JointHandle::Fixed(world.add_fixed(Fixed::new(anchor1, anchor2)))
+            }
+            JointKind::BallInSocket => {
+                let anchor1 = Anchor::new(body1, self.anchor1.to_point());
+                let anchor2 = Anchor::new(body2, self.anchor2.to_point());
+                JointHandle::BallInSocket(world.add_ball_in_socket(BallInSocket::new(anchor1,
+                                                                                     anchor2)))
+            }
+        }
+    }
+
+    // Same joint with its frames referring to bodies by what `f` returns
+    pub fn map_bodies<C, F>(&self, f: F) -> JointDesc<C>
+        where F: Fn(&B) -> C
+    {
+        JointDesc {
+            kind: self.kind.clone(),
+            anchor1: self.anchor1.map_body(&f),
+            anchor2: self.anchor2.map_body(&f),
+        }
+    }
+}
+
+// A group of bodies and the joints between them, detached from any world
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub bodies: Vec<BodyDesc>,
+    pub joints: Vec<JointDesc<BodyIndex>>,
+}
+
+impl Blueprint {
+    // Captures `bodies` and every joint in `joints` that doesn't depend on a body outside of them
+    pub fn capture<'a, I>(bodies: &[RigidBodyHandle<f64>], joints: I) -> Blueprint
+        where I: IntoIterator<Item = &'a JointHandle>
+    {
+        let mut captured = vec![];
+        let mut descs = vec![];
+        for body in bodies {
+            if let Some(desc) = BodyDesc::from_body(&body.borrow()) {
+                captured.push(body.clone());
+                descs.push(desc);
+            }
+        }
+
+        let index_of = |b: &RigidBodyHandle<f64>| {
+            captured.iter().position(|c| Rc::ptr_eq(c, b)).map(BodyIndex)
+        };
+        let joints = joints.into_iter()
+            .filter_map(|joint| JointDesc::from_joint(joint, &index_of))
+            .collect();
+
+        Blueprint {
+            bodies: descs,
+            joints: joints,
+        }
+    }
+
+    pub fn center(&self) -> na::Vector2<f64> {
+        let mut center: na::Vector2<f64> = na::zero();
+        for body in &self.bodies {
+            center += na::Vector2::new(body.position[0], body.position[1]);
+        }
+
+        if self.bodies.is_empty() {
+            center
+        } else {
+            center / self.bodies.len() as f64
+        }
+    }
+
+    // Drops planes, joints attached to one get anchored to the world where the plane was. A saved
+    // scene usually has its own ground, which shouldn't come along when importing it
+    pub fn remove_planes(&mut self) {
+        let mut new_index = vec![];
+        let mut next = 0;
+        for body in &self.bodies {
+            if let ShapeDesc::Plane { .. } = body.shape {
+                new_index.push(None);
+            } else {
+                new_index.push(Some(next));
+                next += 1;
+            }
+        }
+
+        for joint in &mut self.joints {
+            for frame in &mut [&mut joint.anchor1, &mut joint.anchor2] {
+                if let Some(BodyIndex(i)) = frame.body {
+                    if new_index[i].is_none() {
+                        let plane = &self.bodies[i];
+                        let position = isometry(&plane.position, &plane.rotation);
+                        **frame = FrameDesc::from_isometry(None, &(position * frame.to_isometry()));
+                    }
+                }
+
+                frame.body = frame.body.and_then(|BodyIndex(i)| new_index[i]).map(BodyIndex);
+            }
+        }
+
+        let bodies = mem::replace(&mut self.bodies, vec![]);
+        self.bodies = bodies.into_iter()
+            .zip(new_index)
+            .filter(|&(_, ref index)| index.is_some())
+            .map(|(body, _)| body)
+            .collect();
+    }
+
+    // Moves the whole group, including world anchored joint frames
+    pub fn translate(&mut self, delta: &na::Vector2<f64>) {
+        for body in &mut self.bodies {
+            body.position[0] += delta.x;
+            body.position[1] += delta.y;
+        }
+
+        for joint in &mut self.joints {
+            for frame in &mut [&mut joint.anchor1, &mut joint.anchor2] {
+                if frame.body.is_none() {
+                    frame.position[0] += delta.x;
+                    frame.position[1] += delta.y;
+                }
+            }
+        }
+    }
+}
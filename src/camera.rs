@@ -15,6 +15,14 @@ impl Camera {
         }
     }
 
+    pub fn position(&self) -> na::Vector2<f64> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: na::Vector2<f64>) {
+        self.position = position;
+    }
+
     pub fn zoom(&self) -> f64 {
         self.zoom
     }
@@ -23,6 +31,10 @@ impl Camera {
         self.zoom = zoom;
     }
 
+    pub fn size(&self) -> na::Vector2<f64> {
+        self.size
+    }
+
     pub fn set_size(&mut self, width: u32, height: u32) {
         self.size.x = width as f64;
         self.size.y = height as f64;
@@ -0,0 +1,204 @@
+use clap::{App, Arg};
+use headless::Format;
+use rand;
+use std::path::PathBuf;
+
+// Everything that can be set from the command line
+pub struct Config {
+    pub scene: Option<PathBuf>,
+    // `None` keeps whatever the scene was saved with
+    pub paused: Option<bool>,
+
+    pub width: u32,
+    pub height: u32,
+    pub samples: u8,
+    pub vsync: bool,
+    // Updates per second, each one steps the physics by `1 / ups` seconds
+    pub ups: u64,
+    pub seed: usize,
+    // Number of edits that can be undone
+    pub history: usize,
+
+    // Logs every input with the update it happened before
+    pub record: Option<PathBuf>,
+    // Plays back a recording instead of taking input from the window
+    pub replay: Option<PathBuf>,
+    // Directory every rendered frame is saved to as a numbered PNG
+    pub frames: Option<PathBuf>,
+    // Scenes ctrl+i imports into the current one
+    pub prefabs: Option<PathBuf>,
+    // Overrides of the default key and mouse bindings
+    pub bindings: Option<PathBuf>,
+
+    // Runs the simulation without opening a window
+    pub headless: bool,
+    pub steps: u64,
+    // Where the state of every body is written after each headless step
+    pub trajectory: Option<PathBuf>,
+    pub format: Format,
+    // Where the scene is saved once a headless run is done
+    pub output: Option<PathBuf>,
+    // Where the last frame of a headless run is saved as a PNG
+    pub screenshot: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn from_args() -> Self {
+        let matches = App::new("ib-rs")
+            .version(crate_version!())
+            .about("2D physics sandbox")
+            .arg(Arg::with_name("SCENE")
+                     .help("Scene file to open, reloaded when it changes. ctrl+s saves back to it"))
+            .arg(Arg::with_name("paused")
+                     .long("paused")
+                     .conflicts_with("running")
+                     .help("Starts with the simulation paused"))
+            .arg(Arg::with_name("running")
+                     .long("running")
+                     .help("Starts with the simulation running"))
+            .arg(Arg::with_name("width")
+                     .long("width")
+                     .value_name("PIXELS")
+                     .default_value("800")
+                     .validator(positive)
+                     .help("Window width"))
+            .arg(Arg::with_name("height")
+                     .long("height")
+                     .value_name("PIXELS")
+                     .default_value("600")
+                     .validator(positive)
+                     .help("Window height"))
+            .arg(Arg::with_name("samples")
+                     .long("samples")
+                     .value_name("N")
+                     .default_value("4")
+                     .help("MSAA samples, 0 disables antialiasing"))
+            .arg(Arg::with_name("no-vsync")
+                     .long("no-vsync")
+                     .help("Disables vsync"))
+            .arg(Arg::with_name("ups")
+                     .long("ups")
+                     .value_name("N")
+                     .default_value("60")
+                     .validator(positive)
+                     .help("Updates per second, each one steps the physics by 1/N seconds"))
+            .arg(Arg::with_name("seed")
+                     .long("seed")
+                     .value_name("N")
+                     .help("Seed for the random number generator, picked at random if not given"))
+            .arg(Arg::with_name("history")
+                     .long("history")
+                     .value_name("N")
+                     .default_value("100")
+                     .validator(positive)
+                     .help("Number of edits that can be undone"))
+            .arg(Arg::with_name("record")
+                     .long("record")
+                     .value_name("FILE")
+                     .conflicts_with_all(&["replay", "headless"])
+                     .help("Records the session to FILE so it can be replayed exactly"))
+            .arg(Arg::with_name("replay")
+                     .long("replay")
+                     .value_name("FILE")
+                     .conflicts_with("headless")
+                     .help("Replays a session recorded with --record, other scene options are \
+                            ignored"))
+            .arg(Arg::with_name("frames")
+                     .long("frames")
+                     .value_name("DIR")
+                     .help("Saves every frame to DIR as numbered PNGs, after each step in \
+                            headless mode. F11 toggles this in the window"))
+            .arg(Arg::with_name("prefabs")
+                     .long("prefabs")
+                     .value_name("DIR")
+                     .help("Directory of scenes ctrl+i cycles through to import, prefabs by \
+                            default"))
+            .arg(Arg::with_name("bindings")
+                     .long("bindings")
+                     .value_name("FILE")
+                     .help("JSON file of key and mouse bindings, bindings.json is used if it \
+                            exists"))
+            .arg(Arg::with_name("headless")
+                     .long("headless")
+                     .help("Runs the simulation without opening a window, then exits"))
+            .arg(Arg::with_name("steps")
+                     .long("steps")
+                     .value_name("N")
+                     .default_value("600")
+                     .help("Number of updates in headless mode"))
+            .arg(Arg::with_name("trajectory")
+                     .long("trajectory")
+                     .value_name("FILE")
+                     .help("Writes the state of every dynamic body after each headless step to \
+                            FILE, - for stdout"))
+            .arg(Arg::with_name("format")
+                     .long("format")
+                     .value_name("FORMAT")
+                     .possible_values(&["csv", "jsonl"])
+                     .default_value("csv")
+                     .help("Format of the trajectory, CSV or JSON Lines"))
+            .arg(Arg::with_name("output")
+                     .short("o")
+                     .long("output")
+                     .value_name("FILE")
+                     .help("Saves the scene to FILE once the headless run is done"))
+            .arg(Arg::with_name("screenshot")
+                     .long("screenshot")
+                     .value_name("FILE")
+                     .help("Saves the last frame of the headless run to FILE as a PNG"))
+            .get_matches();
+
+        let paused = if matches.is_present("paused") {
+            Some(true)
+        } else if matches.is_present("running") {
+            Some(false)
+        } else {
+            None
+        };
+
+        let seed = if matches.is_present("seed") {
+            value_t!(matches, "seed", usize).unwrap_or_else(|e| e.exit())
+        } else {
+            let seed = rand::random();
+            // Stdout may be taken by the trajectory
+            eprintln!("using seed {}", seed);
+            seed
+        };
+
+        Config {
+            scene: matches.value_of("SCENE").map(PathBuf::from),
+            paused: paused,
+
+            width: value_t!(matches, "width", u32).unwrap_or_else(|e| e.exit()),
+            height: value_t!(matches, "height", u32).unwrap_or_else(|e| e.exit()),
+            samples: value_t!(matches, "samples", u8).unwrap_or_else(|e| e.exit()),
+            vsync: !matches.is_present("no-vsync"),
+            ups: value_t!(matches, "ups", u64).unwrap_or_else(|e| e.exit()),
+            seed: seed,
+            history: value_t!(matches, "history", usize).unwrap_or_else(|e| e.exit()),
+
+            record: matches.value_of("record").map(PathBuf::from),
+            replay: matches.value_of("replay").map(PathBuf::from),
+            frames: matches.value_of("frames").map(PathBuf::from),
+            prefabs: matches.value_of("prefabs").map(PathBuf::from),
+            bindings: matches.value_of("bindings").map(PathBuf::from),
+
+            headless: matches.is_present("headless"),
+            steps: value_t!(matches, "steps", u64).unwrap_or_else(|e| e.exit()),
+            trajectory: matches.value_of("trajectory").map(PathBuf::from),
+            format: match matches.value_of("format") {
+                Some("jsonl") => Format::JsonLines,
+                _ => Format::Csv,
+            },
+            output: matches.value_of("output").map(PathBuf::from),
+            screenshot: matches.value_of("screenshot").map(PathBuf::from),
+        }
+    }
+}
+
+fn positive(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(String::from("must be a positive integer")),
+    }
+}
@@ -0,0 +1,141 @@
+use gilrs::{self, Gilrs};
+use recording::Event;
+
+// Gamepads aren't reported by the window, they're read with gilrs, which maps every pad it knows
+// to the same layout. Buttons are named by where they sit, so South is A on an Xbox pad and X on
+// a PlayStation one
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// Sticks go from -1 to 1 with y pointing down like the screen, triggers from 0 when released to 1
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+pub const AXIS_COUNT: usize = 6;
+
+pub struct Gamepads {
+    // `None` when gamepads aren't supported here
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                eprintln!("failed to open gamepads, they're ignored: {}", e);
+                None
+            }
+        };
+
+        Gamepads { gilrs: gilrs }
+    }
+
+    // Input from every connected pad since the last call, they all control the same things
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        let gilrs = match self.gilrs {
+            Some(ref mut gilrs) => gilrs,
+            None => return events,
+        };
+
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            match event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = from_button(button) {
+                        events.push(Event::GamepadButton {
+                                        button: button,
+                                        pressed: true,
+                                    });
+                    }
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = from_button(button) {
+                        events.push(Event::GamepadButton {
+                                        button: button,
+                                        pressed: false,
+                                    });
+                    }
+                }
+                // Triggers are analog buttons in gilrs' layout
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    events.push(axis_event(GamepadAxis::LeftTrigger, value));
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    events.push(axis_event(GamepadAxis::RightTrigger, value));
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        gilrs::Axis::LeftStickX => axis_event(GamepadAxis::LeftX, value),
+                        gilrs::Axis::LeftStickY => axis_event(GamepadAxis::LeftY, -value),
+                        gilrs::Axis::RightStickX => axis_event(GamepadAxis::RightX, value),
+                        gilrs::Axis::RightStickY => axis_event(GamepadAxis::RightY, -value),
+                        _ => continue,
+                    };
+                    events.push(axis);
+                }
+                // A pad unplugged mid-tilt would otherwise keep the camera moving
+                gilrs::EventType::Disconnected => {
+                    for &axis in &ALL_AXES {
+                        events.push(axis_event(axis, 0.0));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+}
+
+const ALL_AXES: [GamepadAxis; AXIS_COUNT] = [GamepadAxis::LeftX,
+                                             GamepadAxis::LeftY,
+                                             GamepadAxis::RightX,
+                                             GamepadAxis::RightY,
+                                             GamepadAxis::LeftTrigger,
+                                             GamepadAxis::RightTrigger];
+
+fn axis_event(axis: GamepadAxis, position: f32) -> Event {
+    Event::GamepadAxis {
+        axis: axis,
+        position: position as f64,
+    }
+}
+
+fn from_button(button: gilrs::Button) -> Option<GamepadButton> {
+    Some(match button {
+             gilrs::Button::South => GamepadButton::South,
+             gilrs::Button::East => GamepadButton::East,
+             gilrs::Button::North => GamepadButton::North,
+             gilrs::Button::West => GamepadButton::West,
+             gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+             gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+             gilrs::Button::Select => GamepadButton::Select,
+             gilrs::Button::Start => GamepadButton::Start,
+             gilrs::Button::DPadUp => GamepadButton::DPadUp,
+             gilrs::Button::DPadDown => GamepadButton::DPadDown,
+             gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+             gilrs::Button::DPadRight => GamepadButton::DPadRight,
+             _ => return None,
+         })
+}
@@ -0,0 +1,339 @@
+use na;
+
+// Below this, three points are considered collinear
+const EPSILON: f64 = 1.0e-9;
+
+// Positive when the points wind the same way as the axes (x towards y)
+pub fn signed_area(points: &[na::Point2<f64>]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+
+    area / 2.0
+}
+
+// Shoelace formula
+pub fn polygon_area(points: &[na::Point2<f64>]) -> f64 {
+    na::abs(&signed_area(points))
+}
+
+pub fn centroid(points: &[na::Point2<f64>]) -> na::Vector2<f64> {
+    let mut center: na::Vector2<f64> = na::zero();
+    for p in points {
+        center += p.coords;
+    }
+
+    center / points.len() as f64
+}
+
+pub fn distance_to_segment(point: &na::Vector2<f64>,
+                           a: &na::Vector2<f64>,
+                           b: &na::Vector2<f64>)
+                           -> f64 {
+    let ab = b - a;
+    let length_squared = na::norm_squared(&ab);
+    if length_squared == 0.0 {
+        return na::norm(&(point - a));
+    }
+
+    let t = na::clamp(na::dot(&(point - a), &ab) / length_squared, 0.0, 1.0);
+    na::norm(&(point - (a + ab * t)))
+}
+
+// Ramer-Douglas-Peucker, drops points closer than `tolerance` to the simplified path
+pub fn simplify(points: &[na::Point2<f64>], tolerance: f64) -> Vec<na::Point2<f64>> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let first = points[0].coords;
+    let last = points[points.len() - 1].coords;
+    let mut farthest = 0;
+    let mut max_distance = 0.0;
+    for (i, p) in points.iter().enumerate().skip(1).take(points.len() - 2) {
+        let distance = distance_to_segment(&p.coords, &first, &last);
+        if distance > max_distance {
+            farthest = i;
+            max_distance = distance;
+        }
+    }
+
+    if max_distance <= tolerance {
+        return vec![points[0], points[points.len() - 1]];
+    }
+
+    let mut simplified = simplify(&points[..farthest + 1], tolerance);
+    simplified.pop();
+    simplified.extend(simplify(&points[farthest..], tolerance));
+    simplified
+}
+
+// Splits a simple polygon into convex polygons, `None` if it intersects itself
+pub fn convex_decomposition(points: &[na::Point2<f64>]) -> Option<Vec<Vec<na::Point2<f64>>>> {
+    // Ear clipping would still find ears in a figure eight, the pieces would overlap
+    if !is_simple(points) {
+        return None;
+    }
+
+    let triangles = match triangulate(points) {
+        Some(t) => t,
+        None => return None,
+    };
+
+    // Hertel-Mehlhorn, greedily remove diagonals as long as the pieces stay convex
+    let mut pieces: Vec<Vec<usize>> = triangles.iter().map(|t| t.to_vec()).collect();
+    let mut merged_any = true;
+    while merged_any {
+        merged_any = false;
+
+        'search: for i in 0..pieces.len() {
+            for j in i + 1..pieces.len() {
+                if let Some(merged) = merge(&pieces[i], &pieces[j]) {
+                    if is_convex(points, &merged) {
+                        pieces[i] = merged;
+                        pieces.remove(j);
+                        merged_any = true;
+                        break 'search;
+                    }
+                }
+            }
+        }
+    }
+
+    Some(pieces.iter().map(|piece| piece.iter().map(|&i| points[i]).collect()).collect())
+}
+
+fn cross(a: &na::Point2<f64>, b: &na::Point2<f64>, c: &na::Point2<f64>) -> f64 {
+    let ab = b - a;
+    let bc = c - b;
+    ab.x * bc.y - ab.y * bc.x
+}
+
+// Whether no two edges cross or touch, other than neighbours at the corner they share
+pub fn is_simple(points: &[na::Point2<f64>]) -> bool {
+    let n = points.len();
+    for i in 0..n {
+        for j in i + 2..n {
+            // The first and last edges are neighbours too
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+
+            if segments_intersect(&points[i],
+                                  &points[(i + 1) % n],
+                                  &points[j],
+                                  &points[(j + 1) % n]) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+fn segments_intersect(a: &na::Point2<f64>,
+                      b: &na::Point2<f64>,
+                      c: &na::Point2<f64>,
+                      d: &na::Point2<f64>)
+                      -> bool {
+    let side = |x: f64| if x > EPSILON {
+        1
+    } else if x < -EPSILON {
+        -1
+    } else {
+        0
+    };
+    let abc = side(cross(a, b, c));
+    let abd = side(cross(a, b, d));
+    let cda = side(cross(c, d, a));
+    let cdb = side(cross(c, d, b));
+
+    if abc * abd < 0 && cda * cdb < 0 {
+        return true;
+    }
+
+    // An end lying on the other segment
+    (abc == 0 && in_bounds(c, a, b)) || (abd == 0 && in_bounds(d, a, b)) ||
+    (cda == 0 && in_bounds(a, c, d)) || (cdb == 0 && in_bounds(b, c, d))
+}
+
+// Whether `p` is inside the bounding box of `a` and `b`
+fn in_bounds(p: &na::Point2<f64>, a: &na::Point2<f64>, b: &na::Point2<f64>) -> bool {
+    p.x >= a.x.min(b.x) - EPSILON && p.x <= a.x.max(b.x) + EPSILON &&
+    p.y >= a.y.min(b.y) - EPSILON && p.y <= a.y.max(b.y) + EPSILON
+}
+
+fn is_convex(points: &[na::Point2<f64>], polygon: &[usize]) -> bool {
+    let n = polygon.len();
+    (0..n).all(|i| {
+        let a = &points[polygon[i]];
+        let b = &points[polygon[(i + 1) % n]];
+        let c = &points[polygon[(i + 2) % n]];
+        cross(a, b, c) >= -EPSILON
+    })
+}
+
+fn in_triangle(p: &na::Point2<f64>,
+               a: &na::Point2<f64>,
+               b: &na::Point2<f64>,
+               c: &na::Point2<f64>)
+               -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+// Ear clipping, the triangles wind the same way as positive `signed_area`
+fn triangulate(points: &[na::Point2<f64>]) -> Option<Vec<[usize; 3]>> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let mut indices: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0.0 {
+        indices.reverse();
+    }
+
+    let mut triangles = vec![];
+    while indices.len() > 3 {
+        let (flat, ear) = {
+            let n = indices.len();
+            let corner = |i: usize| (indices[(i + n - 1) % n], indices[i], indices[(i + 1) % n]);
+
+            // Collinear points can never be ears, they get dropped first
+            let flat = (0..n).find(|&i| {
+                let (a, b, c) = corner(i);
+                na::abs(&cross(&points[a], &points[b], &points[c])) < EPSILON
+            });
+
+            let ear = (0..n).find(|&i| {
+                let (a, b, c) = corner(i);
+                cross(&points[a], &points[b], &points[c]) > 0.0 &&
+                !indices.iter().any(|&j| {
+                    j != a && j != b && j != c &&
+                    in_triangle(&points[j], &points[a], &points[b], &points[c])
+                })
+            });
+
+            (flat, ear.map(|i| (i, corner(i))))
+        };
+
+        if let Some(i) = flat {
+            indices.remove(i);
+        } else if let Some((i, (a, b, c))) = ear {
+            triangles.push([a, b, c]);
+            indices.remove(i);
+        } else {
+            return None;
+        }
+    }
+
+    triangles.push([indices[0], indices[1], indices[2]]);
+    Some(triangles)
+}
+
+// Joins two polygons sharing an edge, both must wind the same way
+fn merge(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    for i in 0..a.len() {
+        let u = a[i];
+        let v = a[(i + 1) % a.len()];
+
+        if let Some(j) = b.iter().position(|&x| x == v) {
+            if b[(j + 1) % b.len()] == u {
+                // Walk `a` from `v` around to `u`, then `b` from after `u` to before `v`
+                let mut merged: Vec<usize> =
+                    (0..a.len()).map(|k| a[(i + 1 + k) % a.len()]).collect();
+                merged.extend((2..b.len()).map(|k| b[(j + k) % b.len()]));
+                return Some(merged);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use na;
+    use super::*;
+
+    fn points(coords: &[[f64; 2]]) -> Vec<na::Point2<f64>> {
+        coords.iter().map(|c| na::Point2::new(c[0], c[1])).collect()
+    }
+
+    // An L made of six corners, concave at (1, 1)
+    fn l_shape() -> Vec<na::Point2<f64>> {
+        points(&[[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]])
+    }
+
+    #[test]
+    fn simplify_drops_points_near_the_line() {
+        let line = points(&[[0.0, 0.0], [1.0, 0.01], [2.0, -0.01], [3.0, 0.0]]);
+        assert_eq!(simplify(&line, 0.1), points(&[[0.0, 0.0], [3.0, 0.0]]));
+    }
+
+    #[test]
+    fn simplify_keeps_corners() {
+        let corner = points(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [2.0, 1.0], [2.0, 2.0]]);
+        assert_eq!(simplify(&corner, 0.1),
+                   points(&[[0.0, 0.0], [2.0, 0.0], [2.0, 2.0]]));
+    }
+
+    #[test]
+    fn triangulate_covers_the_polygon() {
+        let outline = l_shape();
+        let triangles = triangulate(&outline).unwrap();
+        assert_eq!(triangles.len(), outline.len() - 2);
+
+        let area: f64 = triangles.iter()
+            .map(|t| signed_area(&[outline[t[0]], outline[t[1]], outline[t[2]]]))
+            .sum();
+        assert!((area - polygon_area(&outline)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn triangulate_either_winding() {
+        let mut outline = l_shape();
+        outline.reverse();
+        let triangles = triangulate(&outline).unwrap();
+        assert!(triangles.iter()
+                    .all(|t| signed_area(&[outline[t[0]], outline[t[1]], outline[t[2]]]) > 0.0));
+    }
+
+    #[test]
+    fn convex_decomposition_of_convex_is_itself() {
+        let square = points(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        let pieces = convex_decomposition(&square).unwrap();
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].len(), 4);
+    }
+
+    #[test]
+    fn convex_decomposition_of_concave() {
+        let outline = l_shape();
+        let pieces = convex_decomposition(&outline).unwrap();
+        assert_eq!(pieces.len(), 2);
+
+        let area: f64 = pieces.iter().map(|p| polygon_area(p)).sum();
+        assert!((area - polygon_area(&outline)).abs() < EPSILON);
+        for piece in &pieces {
+            let indices: Vec<usize> = (0..piece.len()).collect();
+            assert!(is_convex(piece, &indices));
+        }
+    }
+
+    #[test]
+    fn convex_decomposition_rejects_figure_eight() {
+        let figure_eight = points(&[[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0]]);
+        assert!(convex_decomposition(&figure_eight).is_none());
+    }
+
+    #[test]
+    fn convex_decomposition_rejects_touching_edges() {
+        // Two corners meet at (1, 1), pinching it into two triangles
+        let pinched = points(&[[0.0, 0.0], [2.0, 0.0], [1.0, 1.0], [2.0, 2.0], [0.0, 2.0],
+                               [1.0, 1.0]]);
+        assert!(convex_decomposition(&pinched).is_none());
+    }
+}
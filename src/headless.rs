@@ -0,0 +1,177 @@
+use app;
+use blueprint::BodyId;
+use color;
+use config::Config;
+use raster::{self, FrameSequence};
+use serde_json;
+use state::{Game, State};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+#[derive(Copy, Clone)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+// State of a body after a step, one line of the trajectory
+#[derive(Serialize)]
+struct Sample {
+    step: u64,
+    time: f64,
+    body: BodyId,
+    x: f64,
+    y: f64,
+    angle: f64,
+    lin_vel_x: f64,
+    lin_vel_y: f64,
+    ang_vel: f64,
+    sleeping: bool,
+}
+
+// Steps the simulation `config.steps` times without a window
+pub fn run(config: &Config) {
+    let mut game = app::build_game(config);
+    // Nothing would happen otherwise
+    game.set_paused(config.paused.unwrap_or(false));
+
+    let mut trajectory = match config.trajectory {
+        Some(ref path) => {
+            let mut out = create_output(path).unwrap_or_else(|e| fail("failed to create", path, e));
+            if let Err(e) = write_header(&mut *out, config.format) {
+                fail("failed to write", path, e);
+            }
+
+            Some((path, out))
+        }
+        None => None,
+    };
+
+    let mut frames = match config.frames {
+        Some(ref dir) => {
+            Some(FrameSequence::new(dir).unwrap_or_else(|e| fail("failed to create", dir, e)))
+        }
+        None => None,
+    };
+
+    let dt = 1.0 / config.ups as f64;
+    for step in 0..config.steps + 1 {
+        // The first line is the state before anything moved
+        if step > 0 {
+            game.update(dt);
+        }
+
+        if let Some((path, ref mut out)) = trajectory {
+            if let Err(e) = write_samples(&mut **out, config.format, &game, step, dt) {
+                fail("failed to write", path, e);
+            }
+        }
+
+        if let Some(ref mut frames) = frames {
+            let canvas =
+                raster::capture(&game, config.width, config.height, color::CORNFLOWER_BLUE);
+            if let Err(e) = frames.save(&canvas) {
+                fail("failed to save frame in", config.frames.as_ref().unwrap(), e);
+            }
+        }
+    }
+
+    if let Some((path, mut out)) = trajectory {
+        if let Err(e) = out.flush() {
+            fail("failed to write", path, e);
+        }
+    }
+
+    if let Some(ref path) = config.screenshot {
+        let canvas = raster::capture(&game, config.width, config.height, color::CORNFLOWER_BLUE);
+        if let Err(e) = canvas.save(path) {
+            fail("failed to save", path, e);
+        }
+    }
+
+    if let Some(ref path) = config.output {
+        if let Err(e) = game.to_scene().save(path) {
+            fail("failed to save", path, e);
+        }
+    }
+}
+
+// `-` writes to stdout
+fn create_output(path: &Path) -> io::Result<Box<Write>> {
+    if path == Path::new("-") {
+        Ok(Box::new(BufWriter::new(io::stdout())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+fn write_header(out: &mut Write, format: Format) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(out,
+                     "step,time,body,x,y,angle,lin_vel_x,lin_vel_y,ang_vel,sleeping")
+        }
+        Format::JsonLines => Ok(()),
+    }
+}
+
+// Static bodies never move, so only dynamic ones are written
+fn write_samples(out: &mut Write,
+                 format: Format,
+                 game: &Game,
+                 step: u64,
+                 dt: f64)
+                 -> io::Result<()> {
+    for (id, body) in game.bodies() {
+        let rb = body.borrow();
+        if rb.is_static() {
+            continue;
+        }
+
+        let position = rb.position();
+        let lin_vel = rb.lin_vel();
+        let sample = Sample {
+            step: step,
+            time: step as f64 * dt,
+            body: id,
+            x: position.translation.vector.x,
+            y: position.translation.vector.y,
+            angle: position.rotation.angle(),
+            lin_vel_x: lin_vel.x,
+            lin_vel_y: lin_vel.y,
+            ang_vel: rb.ang_vel().x,
+            sleeping: !rb.is_active(),
+        };
+
+        match format {
+            Format::Csv => {
+                writeln!(out,
+                         "{},{},{},{},{},{},{},{},{},{}",
+                         sample.step,
+                         sample.time,
+                         sample.body,
+                         sample.x,
+                         sample.y,
+                         sample.angle,
+                         sample.lin_vel_x,
+                         sample.lin_vel_y,
+                         sample.ang_vel,
+                         sample.sleeping)?
+            }
+            Format::JsonLines => {
+                serde_json::to_writer(&mut *out, &sample)?;
+                writeln!(out, "")?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn fail<E: fmt::Display>(what: &str, path: &Path, e: E) -> ! {
+    eprintln!("{} {}: {}", what, path.display(), e);
+    process::exit(1);
+}
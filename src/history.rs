@@ -0,0 +1,250 @@
+use blueprint::{BodyDesc, BodyId, JointDesc, JointId, body_color, is_kinematic};
+use graphics::types::Color;
+use na;
+use nphysics2d::object::RigidBody;
+use scene::LabelDesc;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+// Number of edits that can be undone unless set on the command line
+pub const DEFAULT_LIMIT: usize = 100;
+
+// A reversible edit of the world
+#[derive(Clone)]
+pub enum Command {
+    AddBody { id: BodyId, desc: BodyDesc },
+    RemoveBody { id: BodyId, desc: BodyDesc },
+    AddJoint { id: JointId, desc: JointDesc<BodyId> },
+    RemoveJoint { id: JointId, desc: JointDesc<BodyId> },
+    // Labels have no id, the one to remove is found by its description
+    AddLabel { desc: LabelDesc<BodyId> },
+    RemoveLabel { desc: LabelDesc<BodyId> },
+    SetTransform {
+        id: BodyId,
+        before: na::Isometry2<f64>,
+        after: na::Isometry2<f64>,
+    },
+    SetMotion {
+        id: BodyId,
+        before: Motion,
+        after: Motion,
+    },
+    SetMaterial {
+        id: BodyId,
+        before: Material,
+        after: Material,
+    },
+    // Applied in order and undone in reverse order
+    Group(Vec<Command>),
+}
+
+// Everything the inspector can change about a body. The two halves are edited apart, the
+// simulation keeps changing the motion while the material stays as it was set
+#[derive(Copy, Clone, PartialEq)]
+pub struct Properties {
+    pub motion: Motion,
+    pub material: Material,
+}
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Motion {
+    pub position: [f64; 2],
+    pub rotation: f64,
+    pub lin_vel: [f64; 2],
+    pub ang_vel: f64,
+}
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    // `None` for static bodies
+    pub density: Option<f64>,
+    pub kinematic: bool,
+    pub restitution: f64,
+    pub friction: f64,
+    pub margin: f64,
+    pub color: Color,
+}
+
+impl Properties {
+    pub fn from_body(rb: &RigidBody<f64>) -> Properties {
+        let position = rb.position();
+        let lin_vel = rb.lin_vel();
+
+        Properties {
+            motion: Motion {
+                position: [position.translation.vector.x, position.translation.vector.y],
+                rotation: position.rotation.angle(),
+                lin_vel: [lin_vel.x, lin_vel.y],
+                ang_vel: rb.ang_vel().x,
+            },
+            material: Material {
+                density: if rb.is_static() {
+                    None
+                } else {
+                    Some(rb.density().unwrap_or(1.0))
+                },
+                kinematic: is_kinematic(rb),
+                restitution: rb.restitution(),
+                friction: rb.friction(),
+                margin: rb.margin(),
+                color: body_color(rb),
+            },
+        }
+    }
+}
+
+impl Command {
+    pub fn inverse(&self) -> Command {
+        match *self {
+            Command::AddBody { id, ref desc } => {
+                Command::RemoveBody {
+                    id: id,
+                    desc: desc.clone(),
+                }
+            }
+            Command::RemoveBody { id, ref desc } => {
+                Command::AddBody {
+                    id: id,
+                    desc: desc.clone(),
+                }
+            }
+            Command::AddJoint { id, ref desc } => {
+                Command::RemoveJoint {
+                    id: id,
+                    desc: desc.clone(),
+                }
+            }
+            Command::RemoveJoint { id, ref desc } => {
+                Command::AddJoint {
+                    id: id,
+                    desc: desc.clone(),
+                }
+            }
+            Command::AddLabel { ref desc } => Command::RemoveLabel { desc: desc.clone() },
+            Command::RemoveLabel { ref desc } => Command::AddLabel { desc: desc.clone() },
+            Command::SetTransform { id, before, after } => {
+                Command::SetTransform {
+                    id: id,
+                    before: after,
+                    after: before,
+                }
+            }
+            Command::SetMotion { id, before, after } => {
+                Command::SetMotion {
+                    id: id,
+                    before: after,
+                    after: before,
+                }
+            }
+            Command::SetMaterial { id, before, after } => {
+                Command::SetMaterial {
+                    id: id,
+                    before: after,
+                    after: before,
+                }
+            }
+            Command::Group(ref commands) => {
+                Command::Group(commands.iter().rev().map(Command::inverse).collect())
+            }
+        }
+    }
+}
+
+// Cloned into every snapshot of the timeline, commands are shared so cloning only copies pointers
+#[derive(Clone)]
+pub struct History {
+    undo: VecDeque<Rc<Command>>,
+    redo: Vec<Rc<Command>>,
+    // Oldest commands are forgotten past this many
+    limit: usize,
+    // Whether property edits are still being folded into the last command
+    merging: bool,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: vec![],
+            limit: limit,
+            merging: false,
+        }
+    }
+
+    // Records a command that was just applied
+    pub fn push(&mut self, command: Command) {
+        self.merging = false;
+        self.redo.clear();
+        self.undo.push_back(Rc::new(command));
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    // Same as `push`, except a property edit of the body the last one edited is folded into it
+    // when it changes the same half of the properties, until `end_merge`. Dragging a slider in the
+    // inspector is then a single undo step
+    pub fn push_merged(&mut self, command: Command) {
+        if self.merging && self.redo.is_empty() {
+            // Copied first if a snapshot still holds it as it was
+            let merged = match (self.undo.back_mut().map(Rc::make_mut), &command) {
+                (Some(&mut Command::SetMotion { id, ref mut after, .. }),
+                 &Command::SetMotion { id: new_id, after: new_after, .. }) => {
+                    if id == new_id {
+                        *after = new_after;
+                    }
+                    id == new_id
+                }
+                (Some(&mut Command::SetMaterial { id, ref mut after, .. }),
+                 &Command::SetMaterial { id: new_id, after: new_after, .. }) => {
+                    if id == new_id {
+                        *after = new_after;
+                    }
+                    id == new_id
+                }
+                _ => false,
+            };
+
+            if merged {
+                return;
+            }
+        }
+
+        self.push(command);
+        self.merging = true;
+    }
+
+    pub fn end_merge(&mut self) {
+        self.merging = false;
+    }
+
+    // Returns the command to apply to undo the last edit
+    pub fn undo(&mut self) -> Option<Command> {
+        self.merging = false;
+        let command = match self.undo.pop_back() {
+            Some(c) => c,
+            None => return None,
+        };
+
+        let inverse = command.inverse();
+        self.redo.push(command);
+        Some(inverse)
+    }
+
+    // Returns the command to apply to redo the last undone edit
+    pub fn redo(&mut self) -> Option<Command> {
+        let command = match self.redo.pop() {
+            Some(c) => c,
+            None => return None,
+        };
+
+        self.undo.push_back(command.clone());
+        Some((*command).clone())
+    }
+
+    pub fn clear(&mut self) {
+        self.merging = false;
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
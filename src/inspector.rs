@@ -0,0 +1,191 @@
+use conrod::{self, widget, Colorable, Labelable, Positionable, Sizeable, Widget};
+use history::Properties;
+use std::f64::consts::PI;
+
+widget_ids! {
+    pub struct Ids {
+        panel,
+        x,
+        y,
+        rotation,
+        lin_vel_x,
+        lin_vel_y,
+        ang_vel,
+        kinds[],
+        density,
+        friction,
+        restitution,
+        margin,
+        red,
+        green,
+        blue,
+    }
+}
+
+const KINDS: [&'static str; 3] = ["Static", "Dynamic", "Kinematic"];
+
+pub const PANEL_WIDTH: f64 = 200.0;
+const ROW_HEIGHT: f64 = 24.0;
+const MARGIN: f64 = 4.0;
+const FONT_SIZE: u32 = 12;
+
+// Far enough that nothing in the world is out of reach
+const MAX_POSITION: f64 = 1000.0;
+const MAX_VELOCITY: f64 = 100.0;
+
+impl Ids {
+    // Lists of ids are empty until they're given a size
+    pub fn resize(&mut self, generator: &mut widget::id::Generator) {
+        self.kinds.resize(KINDS.len(), generator);
+    }
+}
+
+// Side panel below `top`, returns the edited properties when something was changed
+pub fn set(ui: &mut conrod::UiCell,
+           ids: &Ids,
+           top: f64,
+           properties: &Properties)
+           -> Option<Properties> {
+    let mut motion = properties.motion;
+    let mut material = properties.material;
+    let width = PANEL_WIDTH - MARGIN * 2.0;
+
+    widget::Canvas::new()
+        .w_h(PANEL_WIDTH, ui.win_h - top)
+        .bottom_right_of(ui.window)
+        .color(conrod::color::rgba(0.0, 0.0, 0.0, 0.6))
+        .set(ids.panel, ui);
+
+    // Rows are stacked from the top of the panel
+    macro_rules! dialer {
+        ($id:expr, $label:expr, $value:expr, $max:expr) => (
+            widget::NumberDialer::new($value, -$max, $max, 2)
+                .label($label)
+                .label_font_size(FONT_SIZE)
+                .w_h(width, ROW_HEIGHT)
+                .down(MARGIN)
+                .set($id, ui)
+        );
+    }
+
+    if let Some(x) = widget::NumberDialer::new(motion.position[0], -MAX_POSITION, MAX_POSITION, 2)
+           .label("x")
+           .label_font_size(FONT_SIZE)
+           .w_h(width, ROW_HEIGHT)
+           .mid_top_with_margin_on(ids.panel, MARGIN)
+           .set(ids.x, ui) {
+        motion.position[0] = x;
+    }
+    if let Some(y) = dialer!(ids.y, "y", motion.position[1], MAX_POSITION) {
+        motion.position[1] = y;
+    }
+    if let Some(rotation) = dialer!(ids.rotation, "rotation", motion.rotation, PI) {
+        motion.rotation = rotation;
+    }
+    if let Some(x) = dialer!(ids.lin_vel_x, "velocity x", motion.lin_vel[0], MAX_VELOCITY) {
+        motion.lin_vel[0] = x;
+    }
+    if let Some(y) = dialer!(ids.lin_vel_y, "velocity y", motion.lin_vel[1], MAX_VELOCITY) {
+        motion.lin_vel[1] = y;
+    }
+    if let Some(ang_vel) = dialer!(ids.ang_vel, "spin", motion.ang_vel, MAX_VELOCITY) {
+        motion.ang_vel = ang_vel;
+    }
+
+    let kind = match material.density {
+        None => 0,
+        Some(_) if !material.kinematic => 1,
+        Some(_) => 2,
+    };
+    let button_width = (width - MARGIN * (KINDS.len() - 1) as f64) / KINDS.len() as f64;
+    for (i, &name) in KINDS.iter().enumerate() {
+        let button = widget::Button::new()
+            .label(name)
+            .label_font_size(FONT_SIZE)
+            .w_h(button_width, ROW_HEIGHT)
+            .color(if i == kind {
+                       conrod::color::LIGHT_YELLOW
+                   } else {
+                       conrod::color::WHITE
+                   });
+        let button = if i == 0 {
+            button.down_from(ids.ang_vel, MARGIN).align_left_of(ids.ang_vel)
+        } else {
+            button.right_from(ids.kinds[i - 1], MARGIN)
+        };
+
+        for _click in button.set(ids.kinds[i], ui) {
+            material.density = if i == 0 {
+                None
+            } else {
+                Some(material.density.unwrap_or(1.0))
+            };
+            material.kinematic = i == 2;
+        }
+    }
+
+    macro_rules! slider {
+        ($id:expr, $label:expr, $value:expr, $min:expr, $max:expr) => (
+            widget::Slider::new($value, $min, $max)
+                .label(&format!("{} {:.2}", $label, $value))
+                .label_font_size(FONT_SIZE)
+                .w_h(width, ROW_HEIGHT)
+                .down(MARGIN)
+                .set($id, ui)
+        );
+    }
+
+    // Static bodies have no mass, the slider is still shown so the rows don't move
+    let density = material.density.unwrap_or(0.0);
+    let density_slider = widget::Slider::new(density, 0.1, 10.0)
+        .label(&format!("density {:.2}", density))
+        .label_font_size(FONT_SIZE)
+        .w_h(width, ROW_HEIGHT)
+        .down_from(ids.kinds[0], MARGIN)
+        .align_left_of(ids.kinds[0])
+        .set(ids.density, ui);
+    if let (Some(density), Some(_)) = (density_slider, material.density) {
+        material.density = Some(density);
+    }
+
+    if let Some(friction) = slider!(ids.friction, "friction", material.friction, 0.0, 2.0) {
+        material.friction = friction;
+    }
+    if let Some(restitution) = slider!(ids.restitution,
+                                       "restitution",
+                                       material.restitution,
+                                       0.0,
+                                       1.0) {
+        material.restitution = restitution;
+    }
+    if let Some(margin) = slider!(ids.margin, "margin", material.margin, 0.0, 0.5) {
+        material.margin = margin;
+    }
+
+    // The color sliders are filled with their channel
+    let channels = [(ids.red, "red", conrod::color::RED),
+                    (ids.green, "green", conrod::color::GREEN),
+                    (ids.blue, "blue", conrod::color::BLUE)];
+    for (i, &(id, label, color)) in channels.iter().enumerate() {
+        let value = material.color[i];
+        if let Some(value) = widget::Slider::new(value, 0.0, 1.0)
+               .label(&format!("{} {:.2}", label, value))
+               .label_font_size(FONT_SIZE)
+               .w_h(width, ROW_HEIGHT)
+               .down(MARGIN)
+               .color(color)
+               .set(id, ui) {
+            material.color[i] = value;
+        }
+    }
+
+    let edited = Properties {
+        motion: motion,
+        material: material,
+    };
+    if edited != *properties {
+        Some(edited)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,72 @@
+use na;
+use nphysics2d::object::RigidBodyHandle;
+use std::rc::Rc;
+
+// A piece of text placed in the world, optionally following a body
+pub struct Label {
+    pub text: String,
+    // Start of the baseline, local to `body` when attached and in world space otherwise
+    position: na::Point2<f64>,
+    // Height of the text in world units
+    pub size: f64,
+    body: Option<RigidBodyHandle<f64>>,
+}
+
+impl Label {
+    pub fn new(position: na::Point2<f64>,
+               size: f64,
+               body: Option<RigidBodyHandle<f64>>)
+               -> Self {
+        let position = match body {
+            Some(ref b) => b.borrow().position().inverse() * position,
+            None => position,
+        };
+
+        Label {
+            text: String::new(),
+            position: position,
+            size: size,
+            body: body,
+        }
+    }
+
+    // `position` is already local to `body` when attached
+    pub fn from_local(text: String,
+                      position: na::Point2<f64>,
+                      size: f64,
+                      body: Option<RigidBodyHandle<f64>>)
+                      -> Self {
+        Label {
+            text: text,
+            position: position,
+            size: size,
+            body: body,
+        }
+    }
+
+    pub fn local_position(&self) -> na::Point2<f64> {
+        self.position
+    }
+
+    pub fn body(&self) -> Option<&RigidBodyHandle<f64>> {
+        self.body.as_ref()
+    }
+
+    pub fn world_position(&self) -> na::Point2<f64> {
+        match self.body {
+            Some(ref b) => b.borrow().position() * self.position,
+            None => self.position,
+        }
+    }
+
+    pub fn is_attached_to(&self, body: &RigidBodyHandle<f64>) -> bool {
+        self.body.as_ref().map_or(false, |b| Rc::ptr_eq(b, body))
+    }
+
+    // Keeps the label where it was on `body` once that's rebuilt as `new_body`
+    pub fn reattach(&mut self, body: &RigidBodyHandle<f64>, new_body: &RigidBodyHandle<f64>) {
+        if self.is_attached_to(body) {
+            self.body = Some(new_body.clone());
+        }
+    }
+}
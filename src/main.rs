@@ -2,21 +2,54 @@ extern crate piston;
 extern crate graphics;
 extern crate opengl_graphics;
 extern crate glutin_window;
+#[macro_use]
+extern crate conrod;
 
 extern crate rand;
 extern crate fps_counter;
+extern crate gilrs;
+extern crate image;
+extern crate rusttype;
+#[macro_use]
+extern crate clap;
 
 extern crate nphysics2d;
 extern crate ncollide;
 extern crate nalgebra as na;
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
 mod app;
+mod bindings;
+mod blueprint;
 mod camera;
 mod color;
+mod config;
+mod gamepad;
+mod geometry;
+mod headless;
+mod history;
+mod inspector;
+mod label;
+mod raster;
+mod recording;
+mod recovery;
+mod scene;
 mod state;
+mod svg;
+mod timeline;
+mod toolbar;
 mod view;
 
 fn main() {
-    let mut app = app::App::new();
-    app.run();
+    let config = config::Config::from_args();
+    if config.headless {
+        headless::run(&config);
+    } else {
+        let mut app = app::App::new(&config);
+        app.run();
+    }
 }
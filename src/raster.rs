@@ -0,0 +1,238 @@
+use graphics::{self, Context, DrawState, Graphics, ImageSize};
+use graphics::character::{Character, CharacterCache};
+use graphics::types::{Color, FontSize};
+use image::{ImageBuffer, Rgba};
+use rusttype::{self, Font, FontCollection, Scale};
+use state::State;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+type RgbaImage = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Software implementation of `Graphics`, draws the same triangles as `GlGraphics` into an image
+pub struct Canvas {
+    image: RgbaImage,
+}
+
+pub struct Texture {
+    image: RgbaImage,
+}
+
+impl ImageSize for Texture {
+    fn get_size(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Canvas { image: ImageBuffer::new(width, height) }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.image.save(path)
+    }
+
+    // From normalized device coordinates to pixels
+    fn to_pixels(&self, vertex: &[f32; 2]) -> [f32; 2] {
+        let (width, height) = self.image.dimensions();
+        [(vertex[0] + 1.0) / 2.0 * width as f32, (1.0 - vertex[1]) / 2.0 * height as f32]
+    }
+
+    // Calls `f` with the barycentric coordinates of every pixel center inside the triangle
+    fn fill_triangle<F>(&mut self, triangle: &[[f32; 2]], mut f: F)
+        where F: FnMut(&mut Rgba<u8>, [f32; 3])
+    {
+        let a = self.to_pixels(&triangle[0]);
+        let b = self.to_pixels(&triangle[1]);
+        let c = self.to_pixels(&triangle[2]);
+        let area = edge(&a, &b, &c);
+        if area == 0.0 {
+            return;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+        let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as u32).min(width);
+        let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as u32).min(height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                // Dividing by the area makes these positive inside whatever the winding
+                let wa = edge(&b, &c, &p) / area;
+                let wb = edge(&c, &a, &p) / area;
+                let wc = edge(&a, &b, &p) / area;
+                if wa >= 0.0 && wb >= 0.0 && wc >= 0.0 {
+                    f(self.image.get_pixel_mut(x, y), [wa, wb, wc]);
+                }
+            }
+        }
+    }
+}
+
+impl Graphics for Canvas {
+    type Texture = Texture;
+
+    fn clear_color(&mut self, color: Color) {
+        let pixel = Rgba([channel(color[0]),
+                          channel(color[1]),
+                          channel(color[2]),
+                          channel(color[3])]);
+        for p in self.image.pixels_mut() {
+            *p = pixel;
+        }
+    }
+
+    fn clear_stencil(&mut self, _value: u8) {}
+
+    // Blending is always on and the stencil is ignored, which is all the game needs
+    fn tri_list<F>(&mut self, _draw_state: &DrawState, color: &[f32; 4], mut f: F)
+        where F: FnMut(&mut FnMut(&[[f32; 2]]))
+    {
+        f(&mut |vertices| {
+            for triangle in vertices.chunks(3).filter(|t| t.len() == 3) {
+                self.fill_triangle(triangle, |pixel, _| blend(pixel, color));
+            }
+        });
+    }
+
+    fn tri_list_uv<F>(&mut self,
+                      _draw_state: &DrawState,
+                      color: &[f32; 4],
+                      texture: &Texture,
+                      mut f: F)
+        where F: FnMut(&mut FnMut(&[[f32; 2]], &[[f32; 2]]))
+    {
+        let (width, height) = texture.image.dimensions();
+        f(&mut |vertices, uvs| {
+            for (triangle, uv) in vertices.chunks(3).zip(uvs.chunks(3)) {
+                if triangle.len() < 3 || uv.len() < 3 {
+                    continue;
+                }
+
+                self.fill_triangle(triangle, |pixel, w| {
+                    // Nearest texel at the interpolated texture coordinates
+                    let u = uv[0][0] * w[0] + uv[1][0] * w[1] + uv[2][0] * w[2];
+                    let v = uv[0][1] * w[0] + uv[1][1] * w[1] + uv[2][1] * w[2];
+                    let x = ((u * width as f32) as u32).min(width.saturating_sub(1));
+                    let y = ((v * height as f32) as u32).min(height.saturating_sub(1));
+                    let texel = texture.image.get_pixel(x, y);
+                    let texel_color = [color[0] * texel.data[0] as f32 / 255.0,
+                                       color[1] * texel.data[1] as f32 / 255.0,
+                                       color[2] * texel.data[2] as f32 / 255.0,
+                                       color[3] * texel.data[3] as f32 / 255.0];
+                    blend(pixel, &texel_color);
+                });
+            }
+        });
+    }
+}
+
+fn edge(a: &[f32; 2], b: &[f32; 2], p: &[f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+fn channel(c: f32) -> u8 {
+    (c.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+// Source over
+fn blend(pixel: &mut Rgba<u8>, color: &[f32; 4]) {
+    let alpha = color[3].max(0.0).min(1.0);
+    for i in 0..3 {
+        let dst = pixel.data[i] as f32 / 255.0;
+        pixel.data[i] = channel(color[i] * alpha + dst * (1.0 - alpha));
+    }
+    let dst = pixel.data[3] as f32 / 255.0;
+    pixel.data[3] = channel(alpha + dst * (1.0 - alpha));
+}
+
+// Software counterpart of `opengl_graphics::GlyphCache`, glyphs are white with coverage in alpha
+pub struct GlyphCache {
+    font: Font<'static>,
+    glyphs: HashMap<(FontSize, char), ([f64; 2], [f64; 2], Texture)>,
+}
+
+impl GlyphCache {
+    pub fn from_bytes(data: &'static [u8]) -> io::Result<Self> {
+        let font = match FontCollection::from_bytes(data).into_font() {
+            Some(font) => font,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid font")),
+        };
+
+        Ok(GlyphCache {
+               font: font,
+               glyphs: HashMap::new(),
+           })
+    }
+}
+
+impl CharacterCache for GlyphCache {
+    type Texture = Texture;
+
+    fn character<'a>(&'a mut self, size: FontSize, ch: char) -> Character<'a, Texture> {
+        let font = &self.font;
+        let glyph = self.glyphs.entry((size, ch)).or_insert_with(|| {
+            let glyph = font.glyph(ch)
+                .unwrap_or_else(|| font.glyph(rusttype::GlyphId(0)).unwrap())
+                .scaled(Scale::uniform(size as f32));
+            let advance = [glyph.h_metrics().advance_width as f64, 0.0];
+            let glyph = glyph.positioned(rusttype::point(0.0, 0.0));
+
+            match glyph.pixel_bounding_box() {
+                Some(bounds) => {
+                    let mut image = ImageBuffer::from_pixel(bounds.width() as u32,
+                                                            bounds.height() as u32,
+                                                            Rgba([255, 255, 255, 0]));
+                    glyph.draw(|x, y, v| {
+                        image.put_pixel(x, y, Rgba([255, 255, 255, channel(v)]))
+                    });
+                    ([bounds.min.x as f64, -bounds.min.y as f64], advance, Texture { image: image })
+                }
+                // Spaces have nothing to draw
+                None => ([0.0, 0.0], advance, Texture { image: ImageBuffer::new(1, 1) }),
+            }
+        });
+
+        Character {
+            offset: glyph.0,
+            size: glyph.1,
+            texture: &glyph.2,
+        }
+    }
+}
+
+// Renders `state` the way the window would show it
+pub fn capture(state: &State, width: u32, height: u32, background: Color) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    graphics::clear(background, &mut canvas);
+    state.render_software(&Context::new_abs(width as f64, height as f64), &mut canvas);
+    canvas
+}
+
+// Numbered frames written to a directory, for turning into videos
+pub struct FrameSequence {
+    dir: PathBuf,
+    next: u64,
+}
+
+impl FrameSequence {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FrameSequence {
+               dir: dir,
+               next: 0,
+           })
+    }
+
+    pub fn save(&mut self, canvas: &Canvas) -> io::Result<()> {
+        let path = self.dir.join(format!("frame-{:06}.png", self.next));
+        self.next += 1;
+        canvas.save(path)
+    }
+}
@@ -0,0 +1,179 @@
+use bindings::Bindings;
+use gamepad::{GamepadAxis, GamepadButton};
+use history;
+use piston::input::{Button, Input, Key, Motion, MouseButton};
+use scene::Scene;
+use serde_json;
+use state::{Load, State};
+use toolbar::UiAction;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// First line of a recording, everything needed to rebuild the game it started from
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub seed: usize,
+    pub ups: u64,
+    pub width: u32,
+    pub height: u32,
+    // Undoing has to go back as far as when recording
+    #[serde(default = "default_history")]
+    pub history: usize,
+    // Keys are stored as codes, they have to do what they did when recording
+    #[serde(default)]
+    pub bindings: Bindings,
+    pub scene: Scene,
+}
+
+fn default_history() -> usize {
+    history::DEFAULT_LIMIT
+}
+
+// Input forwarded to the current state, keys and buttons are stored as their codes
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Event {
+    MouseMove { x: f64, y: f64 },
+    MouseScroll { x: f64, y: f64 },
+    MouseButton { button: u32, pressed: bool },
+    Key { key: u32, pressed: bool },
+    Text { text: String },
+    GamepadAxis { axis: GamepadAxis, position: f64 },
+    GamepadButton { button: GamepadButton, pressed: bool },
+    Resize { width: u32, height: u32 },
+    // Click on the toolbar
+    Ui { action: UiAction },
+    // Contents of a file the game read
+    Load { load: Load },
+}
+
+impl Event {
+    pub fn from_input(input: &Input) -> Option<Event> {
+        match *input {
+            Input::Move(Motion::MouseCursor(x, y)) => Some(Event::MouseMove { x: x, y: y }),
+            Input::Move(Motion::MouseScroll(x, y)) => Some(Event::MouseScroll { x: x, y: y }),
+            Input::Press(Button::Mouse(button)) => {
+                Some(Event::MouseButton {
+                         button: button.into(),
+                         pressed: true,
+                     })
+            }
+            Input::Release(Button::Mouse(button)) => {
+                Some(Event::MouseButton {
+                         button: button.into(),
+                         pressed: false,
+                     })
+            }
+            Input::Press(Button::Keyboard(key)) => {
+                Some(Event::Key {
+                         key: key.into(),
+                         pressed: true,
+                     })
+            }
+            Input::Release(Button::Keyboard(key)) => {
+                Some(Event::Key {
+                         key: key.into(),
+                         pressed: false,
+                     })
+            }
+            Input::Text(ref text) => Some(Event::Text { text: text.clone() }),
+            Input::Resize(width, height) => {
+                Some(Event::Resize {
+                         width: width,
+                         height: height,
+                     })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn send_to(&self, state: &mut State) {
+        match *self {
+            Event::MouseMove { x, y } => state.handle_mouse_move(x, y),
+            Event::MouseScroll { x, y } => state.handle_mouse_scroll(x, y),
+            Event::MouseButton { button, pressed } => {
+                state.handle_mouse_button(MouseButton::from(button), pressed)
+            }
+            Event::Key { key, pressed } => state.handle_key(Key::from(key), pressed),
+            Event::Text { ref text } => state.handle_text(text),
+            Event::GamepadAxis { axis, position } => state.handle_gamepad_axis(axis, position),
+            Event::GamepadButton { button, pressed } => {
+                state.handle_gamepad_button(button, pressed)
+            }
+            Event::Resize { width, height } => state.handle_resize(width, height),
+            Event::Ui { action } => state.handle_ui_action(action),
+            Event::Load { ref load } => state.handle_load(load.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    // Number of updates that ran before the event
+    tick: u64,
+    event: Event,
+}
+
+// Writes a header and then one line per event, as JSON Lines
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    // Also returns the header as a replay will read it back, the recorded session has to start
+    // from exactly the same floats
+    pub fn create<P: AsRef<Path>>(path: P, header: &Header) -> io::Result<(Recorder, Header)> {
+        let line = serde_json::to_string(header)?;
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", line)?;
+
+        Ok((Recorder { out: out }, serde_json::from_str(&line)?))
+    }
+
+    pub fn record(&mut self, tick: u64, event: &Event) -> io::Result<()> {
+        let entry = Entry {
+            tick: tick,
+            event: event.clone(),
+        };
+        serde_json::to_writer(&mut self.out, &entry)?;
+        writeln!(self.out, "")
+    }
+}
+
+pub struct Replay {
+    entries: VecDeque<Entry>,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<(Header, Replay)> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "empty recording")),
+        };
+
+        let mut entries = VecDeque::new();
+        for line in lines {
+            let line = line?;
+            if !line.is_empty() {
+                entries.push_back(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok((header, Replay { entries: entries }))
+    }
+
+    // Next event recorded before update number `tick` ran
+    pub fn next_event(&mut self, tick: u64) -> Option<Event> {
+        if self.entries.front().map_or(false, |e| e.tick <= tick) {
+            self.entries.pop_front().map(|e| e.event)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
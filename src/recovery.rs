@@ -0,0 +1,114 @@
+use scene::Scene;
+use state::Game;
+use std::cell::Cell;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+
+// The scene is autosaved here while a marker file says the session is still running. Both are
+// removed on a clean exit, so finding the marker on startup means the last session crashed
+pub struct Recovery {
+    dir: PathBuf,
+    // Set once this session took over the files, a crashed one's are kept until then
+    started: Cell<bool>,
+}
+
+impl Recovery {
+    // `None` when there's no data directory to put the files in
+    pub fn new() -> Option<Self> {
+        let dir = match data_dir() {
+            Some(dir) => dir.join("ib-rs"),
+            None => return None,
+        };
+
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("failed to create {}, autosave is off: {}", dir.display(), e);
+            return None;
+        }
+
+        Some(Recovery {
+                 dir: dir,
+                 started: Cell::new(false),
+             })
+    }
+
+    pub fn scene_path(&self) -> PathBuf {
+        self.dir.join("recovery.json")
+    }
+
+    fn marker_path(&self) -> PathBuf {
+        self.dir.join("running")
+    }
+
+    // What was autosaved by a session that didn't exit cleanly
+    pub fn crashed_scene(&self) -> Option<Scene> {
+        if !self.marker_path().exists() {
+            return None;
+        }
+
+        let path = self.scene_path();
+        match Scene::load(&path) {
+            Ok(scene) => Some(scene),
+            Err(e) => {
+                // Crashed before the first autosave
+                if path.exists() {
+                    eprintln!("failed to open {}: {}", path.display(), e);
+                }
+                None
+            }
+        }
+    }
+
+    // Marks the session as running and autosaves `game` from then on
+    pub fn start(&self, game: &mut Game) {
+        match File::create(self.marker_path()) {
+            Ok(_) => {
+                self.started.set(true);
+                game.set_autosave_path(self.scene_path());
+            }
+            Err(e) => eprintln!("failed to start autosaving: {}", e),
+        }
+    }
+
+    pub fn finish(&self) {
+        if !self.started.get() {
+            return;
+        }
+
+        for path in &[self.scene_path(), self.marker_path()] {
+            match fs::remove_file(path) {
+                Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                    eprintln!("failed to remove {}: {}", path.display(), e)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join("Library/Application Support"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn data_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home_dir().map(|home| home.join(".local/share")),
+    }
+}
+
+#[cfg(unix)]
+fn home_dir() -> Option<PathBuf> {
+    match env::var_os("HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => None,
+    }
+}
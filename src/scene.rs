@@ -0,0 +1,180 @@
+use blueprint::{BodyIndex, Blueprint};
+use serde_json;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// Everything needed to rebuild a `Game`, stored as pretty printed JSON
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Scene {
+    pub gravity: [f64; 2],
+    pub camera: CameraDesc,
+    pub paused: bool,
+    pub world: Blueprint,
+    #[serde(default)]
+    pub labels: Vec<LabelDesc<BodyIndex>>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub position: [f64; 2],
+    pub zoom: f64,
+}
+
+// `B` is how the body is referred to, like in a `FrameDesc`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LabelDesc<B> {
+    pub text: String,
+    // Local to `body` when attached, world space otherwise
+    pub position: [f64; 2],
+    pub size: f64,
+    pub body: Option<B>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneError::Io(ref e) => write!(f, "{}", e),
+            SceneError::Json(ref e) => write!(f, "invalid scene: {}", e),
+        }
+    }
+}
+
+impl error::Error for SceneError {
+    fn description(&self) -> &str {
+        match *self {
+            SceneError::Io(ref e) => e.description(),
+            SceneError::Json(ref e) => e.description(),
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneError::Json(e)
+    }
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let contents = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(contents.as_bytes())?;
+        file.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use state::{Game, State};
+    use super::*;
+
+    // One body of every shape, a kinematic one, margins, rotations, joints to a body and to the
+    // world, and labels on and off a body
+    const SCENE: &'static str = r#"{
+        "gravity": [0.0, 20.0],
+        "camera": { "position": [1.0, -2.0], "zoom": 30.0 },
+        "paused": true,
+        "world": {
+            "bodies": [
+                { "shape": { "Plane": { "normal": [0.0, -1.0] } },
+                  "position": [0.0, 0.0], "rotation": [1.0, 0.0], "lin_vel": [0.0, 0.0],
+                  "ang_vel": 0.0, "density": null, "restitution": 0.3, "friction": 0.6,
+                  "color": [1.0, 1.0, 1.0, 1.0] },
+                { "shape": { "Ball": { "radius": 0.5 } },
+                  "position": [-2.0, -1.1], "rotation": [0.8, 0.6], "lin_vel": [1.5, 0.1],
+                  "ang_vel": 0.25, "density": 2.0, "restitution": 0.5, "friction": 0.2,
+                  "color": [0.5, 0.25, 1.0, 1.0], "margin": 0.125 },
+                { "shape": { "Cuboid": { "half_extents": [1.0, 0.5] } },
+                  "position": [2.0, -1.0], "rotation": [0.5403023058681398, 0.8414709848078965],
+                  "lin_vel": [0.0, 0.0], "ang_vel": 0.0,
+                  "density": 1.0, "restitution": 0.3, "friction": 0.6,
+                  "color": [0.25, 0.5, 0.75, 1.0], "kinematic": true },
+                { "shape": { "Polygon": { "points": [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]] } },
+                  "position": [4.0, -1.0], "rotation": [-0.4161468365471424, 0.9092974268256817],
+                  "lin_vel": [0.0, 0.0], "ang_vel": 0.0,
+                  "density": 1.0, "restitution": 0.3, "friction": 0.6,
+                  "color": [1.0, 0.0, 0.0, 1.0] },
+                { "shape": { "Compound": { "parts": [
+                      { "position": [0.0, 0.0], "rotation": [1.0, 0.0],
+                        "shape": { "Cuboid": { "half_extents": [0.5, 0.5] } } },
+                      { "position": [1.0, 0.0], "rotation": [0.0, 1.0],
+                        "shape": { "Polygon": { "points": [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]] } } }
+                  ] } },
+                  "position": [6.0, -1.0], "rotation": [1.0, 0.0], "lin_vel": [0.0, 0.0],
+                  "ang_vel": 0.0, "density": 0.5, "restitution": 0.3, "friction": 0.6,
+                  "color": [0.0, 1.0, 0.0, 1.0] }
+            ],
+            "joints": [
+                { "kind": "BallInSocket",
+                  "anchor1": { "body": 1, "position": [0.0, 0.0], "rotation": [1.0, 0.0] },
+                  "anchor2": { "body": 2, "position": [-1.0, 0.0], "rotation": [1.0, 0.0] } },
+                { "kind": "Fixed",
+                  "anchor1": { "body": 3, "position": [0.0, 0.0], "rotation": [0.6, -0.8] },
+                  "anchor2": { "body": null, "position": [4.3, -3.0],
+                               "rotation": [0.8, 0.6] } }
+            ]
+        },
+        "labels": [
+            { "text": "ball", "position": [0.0, -1.0], "size": 0.5, "body": 1 },
+            { "text": "free", "position": [3.0, -4.0], "size": 1.0, "body": null }
+        ]
+    }"#;
+
+    #[test]
+    fn scene_round_trips() {
+        let mut game = Game::new(0);
+        game.load_scene(serde_json::from_str(SCENE).unwrap());
+        let scene = game.to_scene();
+        assert_eq!(scene.world.bodies[1].margin, Some(0.125));
+        assert!(scene.world.bodies[2].kinematic);
+        assert_eq!(scene.world.joints.len(), 2);
+        assert_eq!(scene.labels.len(), 2);
+
+        assert_round_trip(&scene);
+    }
+
+    // Whatever the solver leaves in the last bits has to survive as well
+    #[test]
+    fn simulated_scene_round_trips() {
+        let mut game = Game::new(0);
+        game.load_scene(serde_json::from_str(SCENE).unwrap());
+        game.set_paused(false);
+        for _ in 0..30 {
+            game.update(1.0 / 60.0);
+        }
+
+        let scene = game.to_scene();
+        assert!(scene.world.bodies[1].position != [-2.0, -1.1]);
+        assert_round_trip(&scene);
+    }
+
+    fn assert_round_trip(scene: &Scene) {
+        let json = serde_json::to_string(scene).unwrap();
+        let mut loaded = Game::new(1);
+        loaded.load_scene(serde_json::from_str(&json).unwrap());
+        assert_eq!(loaded.to_scene(), *scene);
+    }
+}
@@ -1,19 +1,43 @@
-use super::State;
+use super::{Load, Pause, State, Transition};
+use bindings::{Bindings, Control, Modifiers, Trigger};
+use blueprint::{BodyDesc, BodyId, BodyIndex, Blueprint, FrameDesc, JointDesc, JointHandle, JointId,
+                JointKind, body_color, body_id, is_kinematic, set_body_data};
 use camera::Camera;
 use color;
-use graphics::{self, Context, Transformed};
+use gamepad::{self, GamepadAxis, GamepadButton};
+use graphics::{self, Colored, Context, Graphics, Transformed};
+use geometry;
+use graphics::character::CharacterCache;
+use graphics::types::Color;
+use history::{self, Command, History, Material, Motion, Properties};
+use label::Label;
 use na;
-use ncollide::shape::{Ball2, Cuboid2, Plane2};
+use ncollide::bounding_volume::AABB2;
+use ncollide::shape::{Ball2, Compound2, ConvexHull2, Cuboid2, Plane2, ShapeHandle2};
+use ncollide::transformation;
 use ncollide::world::CollisionGroups;
 use nphysics2d::detection::constraint::Constraint;
 use nphysics2d::detection::joint::{Anchor, Fixed, Joint};
-use nphysics2d::object::{RigidBody, RigidBodyHandle, WorldObject};
+use nphysics2d::object::{RigidBody, RigidBodyHandle, RigidBodyState, WorldObject};
 use nphysics2d::world::World;
-use opengl_graphics::GlGraphics;
+use opengl_graphics::{GlGraphics, GlyphCache};
+use raster::{self, Canvas};
 use piston::input::{Key, MouseButton};
+use rand::{SeedableRng, StdRng};
+use scene::{CameraDesc, LabelDesc, Scene, SceneError};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::f64;
+use std::f64::consts::PI;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use view;
+use std::time::SystemTime;
+use svg::Svg;
+use timeline::{Snapshot, Timeline};
+use toolbar::{Status, Tool, UiAction};
+use view::{self, FONT};
 
 const MAX_CUBOID_WIDTH: f64 = 10.0;
 const MIN_CUBOID_WIDTH: f64 = 0.1;
@@ -24,9 +48,58 @@ const MIN_CUBOID_HEIGHT: f64 = 0.1;
 const MAX_BALL_RADIUS: f64 = 10.0;
 const MIN_BALL_RADIUS: f64 = 0.1;
 
+const MAX_POLYGON_VERTICES: usize = 32;
+const MIN_POLYGON_AREA: f64 = 0.01;
+// Distances in window space between points kept while drawing with the pencil, and under which
+// they are dropped once the outline is simplified
+const DRAW_MIN_SEGMENT: f64 = 4.0;
+const DRAW_SIMPLIFY_TOLERANCE: f64 = 3.0;
+// Distance in window space at which a click snaps to the first vertex and closes the polygon
+const POLYGON_CLOSE_DISTANCE: f64 = 10.0;
+
+// Rotation tool snaps to 15 degree increments while shift is held
+const ROTATION_SNAP: f64 = PI / 12.0;
+
+const DEFAULT_TEXT_SIZE: f64 = 1.0;
+const MIN_TEXT_SIZE: f64 = 0.25;
+const MAX_TEXT_SIZE: f64 = 10.0;
+// Size of the resize handle drawn next to labels, in window space
+const TEXT_HANDLE_SIZE: f64 = 8.0;
+
+// Distance in window space under which a click picks a joint
+const JOINT_PICK_DISTANCE: f64 = 6.0;
+
+// Material of new bodies, the inspector changes it afterwards
+const DEFAULT_DENSITY: f64 = 1.0;
+const DEFAULT_RESTITUTION: f64 = 0.3;
+const DEFAULT_FRICTION: f64 = 0.6;
+
+// A snapshot every 10 steps, keeping the last 50 seconds at 60 steps per second
+const SNAPSHOT_INTERVAL: u64 = 10;
+const SNAPSHOT_LIMIT: usize = 300;
+// Timeline bar along the bottom of the window, in window space
+const TIMELINE_HEIGHT: f64 = 12.0;
+const TIMELINE_MARGIN: f64 = 8.0;
+
+// Where ctrl+s and ctrl+o save and load the scene
+const DEFAULT_SCENE_PATH: &'static str = "scene.json";
+// Seconds between autosaves
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+// Seconds between checks of the watched scene file
+const WATCH_INTERVAL: f64 = 0.5;
+// Scenes that can be imported into the current one with ctrl+i
+const DEFAULT_PREFAB_DIR: &'static str = "prefabs";
+
 const MIN_ZOOM: f64 = 12.0;
 const MAX_ZOOM: f64 = 75.0;
 
+// Worn sticks don't quite center
+const STICK_DEADZONE: f64 = 0.2;
+// Pixels per second with the right stick all the way over
+const CURSOR_SPEED: f64 = 600.0;
+// Zoom is multiplied by this every second a trigger is fully pulled
+const TRIGGER_ZOOM_RATE: f64 = 2.0;
+
 #[derive(Copy, Clone, PartialEq)]
 #[allow(dead_code)]
 enum Action {
@@ -44,11 +117,35 @@ enum Action {
     CreatingText,
     ResizingText,
 
+    Erasing,
+    DrawingPolygon,
+    Inspecting,
+
     None,
 }
 
 pub struct Game {
     world: World<f64>,
+    gravity: na::Vector2<f64>,
+    // Length of a physics step, kept constant whatever the frame rate
+    timestep: f64,
+    // Seeded with --seed so runs can be reproduced, nothing draws from it yet
+    #[allow(dead_code)]
+    rng: StdRng,
+    scene_path: PathBuf,
+    // Where the scene is saved every so often in case of a crash
+    autosave_path: Option<PathBuf>,
+    autosave_timer: f64,
+    // Handed to the state stack once the current update or input is handled
+    transition: Transition,
+    // Modification time of the scene file while it's watched, it's reloaded when this changes
+    watched_modified: Option<SystemTime>,
+    watching: bool,
+    watch_timer: f64,
+    // Files read since the app last asked, they're only acted on once it sends them back
+    loads: Vec<Load>,
+    // Replays get files from the recording and don't write any
+    replaying: bool,
     // Handles conversions between world->window and window->world
     camera: Camera,
 
@@ -71,6 +168,71 @@ pub struct Game {
     current_action: Action,
     action_step: i8,
 
+    // Vertices placed so far with the polygon or pencil tool, in world space
+    polygon_points: Vec<na::Point2<f64>>,
+    // Body picked by the first click of a joint tool
+    joint_body: Option<RigidBodyHandle<f64>>,
+
+    joints: BTreeMap<JointId, JointHandle>,
+
+    next_body_id: BodyId,
+    next_joint_id: JointId,
+    history: History,
+    // Transform of the grabbed or rotated body when the edit started
+    edit_start: na::Isometry2<f64>,
+
+    // Physics steps since the scene was loaded
+    step: u64,
+    timeline: Timeline,
+    // Dragging on the timeline bar
+    scrubbing: bool,
+
+    // Body being edited with the rotate tool, made kinematic until the mouse is released
+    rotating_body: Option<RigidBodyHandle<f64>>,
+    // Orientation of `rotating_body` when the drag started
+    rotation_start_angle: f64,
+    // Angle of the mouse around the body's center when the drag started
+    rotation_start_mouse_angle: f64,
+    // Whether `rotating_body` was kinematic before, it's left that way
+    rotation_was_kinematic: bool,
+
+    // Body shown in the inspector panel, last clicked with the inspect tool
+    inspected: Option<BodyId>,
+
+    // Bodies that move, delete and property tools act on
+    selection: Vec<RigidBodyHandle<f64>>,
+
+    labels: Vec<Label>,
+    // Label receiving typed text
+    editing_label: Option<usize>,
+    // Label being resized with its handle, along with its size and width when the drag started
+    resizing_label: Option<usize>,
+    resize_start_size: f64,
+    resize_start_width: f64,
+    // Only borrowed mutably while measuring or drawing text, loaded on first use so the physics
+    // can run without any graphics
+    glyphs: RefCell<Option<GlyphCache<'static>>>,
+    // Same for screenshots, which are rasterized in software
+    software_glyphs: RefCell<Option<raster::GlyphCache>>,
+
+    // Bodies and joints copied with ctrl+c or ctrl+x
+    clipboard: Option<Blueprint>,
+    // Group following the cursor until it's placed, from the clipboard or an imported scene
+    placing: Option<Blueprint>,
+    // Scenes ctrl+i cycles through, and which one comes next
+    prefab_dir: PathBuf,
+    prefab_index: usize,
+
+    shift_held: bool,
+    ctrl_held: bool,
+    alt_held: bool,
+    // What keys and mouse buttons do
+    bindings: Bindings,
+    // Last position of every gamepad axis, acted on every update
+    gamepad_axes: [f64; gamepad::AXIS_COUNT],
+    // Set while the right stick drives the cursor, which is drawn then as the system one stays put
+    gamepad_cursor: bool,
+
     move_camera_up: bool,
     move_camera_down: bool,
     move_camera_left: bool,
@@ -81,13 +243,94 @@ pub struct Game {
 }
 
 impl Game {
-    pub fn new() -> Self {
-        let mut world = World::new();
-        world.set_gravity(na::Vector2::new(0.0, 30.0));
+    pub fn new(seed: usize) -> Self {
+        let mut game = Game {
+            world: World::new(),
+            gravity: na::Vector2::new(0.0, 30.0),
+            timestep: 1.0 / 60.0,
+            rng: SeedableRng::from_seed(&[seed][..]),
+            scene_path: PathBuf::from(DEFAULT_SCENE_PATH),
+            autosave_path: None,
+            autosave_timer: 0.0,
+            transition: Transition::None,
+            watched_modified: None,
+            watching: false,
+            watch_timer: 0.0,
+            loads: vec![],
+            replaying: false,
+            camera: Camera::new(800, 600),
+
+            paused: true,
+
+            grabbed_object: None,
+            grabbed_object_joint: None,
+
+            mouse_position: na::zero(),
+            mouse_position_world: na::Point2::new(0.0, 0.0),
+            first_click: na::zero(),
+            first_click_world: na::Point2::new(0.0, 0.0),
+
+            current_action: Action::None,
+            action_step: 0,
+
+            polygon_points: vec![],
+            joint_body: None,
+
+            joints: BTreeMap::new(),
+
+            next_body_id: 0,
+            next_joint_id: 0,
+            history: History::new(history::DEFAULT_LIMIT),
+            edit_start: na::Isometry2::identity(),
+
+            step: 0,
+            timeline: Timeline::new(SNAPSHOT_LIMIT),
+            scrubbing: false,
+
+            rotating_body: None,
+            rotation_was_kinematic: false,
+            inspected: None,
+            rotation_start_angle: 0.0,
+            rotation_start_mouse_angle: 0.0,
+
+            selection: vec![],
+
+            labels: vec![],
+            editing_label: None,
+            resizing_label: None,
+            resize_start_size: 0.0,
+            resize_start_width: 0.0,
+            glyphs: RefCell::new(None),
+            software_glyphs: RefCell::new(None),
+
+            clipboard: None,
+            placing: None,
+            prefab_dir: PathBuf::from(DEFAULT_PREFAB_DIR),
+            prefab_index: 0,
+
+            shift_held: false,
+            ctrl_held: false,
+            alt_held: false,
+            bindings: Bindings::default(),
+            gamepad_axes: [0.0; gamepad::AXIS_COUNT],
+            gamepad_cursor: false,
+
+            move_camera_up: false,
+            move_camera_down: false,
+            move_camera_left: false,
+            move_camera_right: false,
+
+            constraints: vec![],
+        };
+
+        game.world.set_gravity(game.gravity);
 
         // Creates the ground
-        let rb = RigidBody::new_static(Plane2::new(na::Vector2::new(0.0, -1.0)), 0.3, 0.6);
-        world.add_rigid_body(rb);
+        let rb = RigidBody::new_static(Plane2::new(na::Vector2::new(0.0, -1.0)),
+                                       DEFAULT_RESTITUTION,
+                                       DEFAULT_FRICTION);
+        let id = game.new_body_id();
+        game.add_body(id, color::WHITE, rb);
 
         // Creating cuboids for pyramid
         let num = 35;
@@ -108,68 +351,1684 @@ impl Game {
                                                     0.3,
                                                     0.6);
                 rb.append_translation(&na::Translation2::new(x, y));
-                world.add_rigid_body(rb);
+                let id = game.new_body_id();
+                game.add_body(id, color::WHITE, rb);
+            }
+        }
+
+        game
+    }
+
+    // Bodies created by the game, ordered by id
+    pub fn bodies(&self) -> Vec<(BodyId, RigidBodyHandle<f64>)> {
+        let mut bodies: Vec<(BodyId, RigidBodyHandle<f64>)> = self.world
+            .rigid_bodies()
+            .filter_map(|b| body_id(b).map(|id| (id, b.clone())))
+            .collect();
+        bodies.sort_by_key(|&(id, _)| id);
+        bodies
+    }
+
+    pub fn to_scene(&self) -> Scene {
+        // Sorted by id, which keeps the file stable between saves
+        let bodies: Vec<RigidBodyHandle<f64>> =
+            self.bodies().into_iter().map(|(_, b)| b).collect();
+
+        let labels = self.labels
+            .iter()
+            .map(|label| {
+                let position = label.local_position();
+                LabelDesc {
+                    text: label.text.clone(),
+                    position: [position.x, position.y],
+                    size: label.size,
+                    body: label.body()
+                        .and_then(|b| bodies.iter().position(|o| Rc::ptr_eq(o, b)))
+                        .map(BodyIndex),
+                }
+            })
+            .collect();
+
+        let camera = self.camera.position();
+        Scene {
+            gravity: [self.gravity.x, self.gravity.y],
+            camera: CameraDesc {
+                position: [camera.x, camera.y],
+                zoom: self.camera.zoom(),
+            },
+            paused: self.paused,
+            world: Blueprint::capture(&bodies, self.joints.values()),
+            labels: labels,
+        }
+    }
+
+    pub fn set_scene_path(&mut self, path: PathBuf) {
+        self.scene_path = path;
+    }
+
+    // Reloads the scene whenever its file changes on disk. Replays get the reloads from the
+    // recording instead
+    pub fn watch_scene_file(&mut self) {
+        if self.replaying {
+            return;
+        }
+
+        self.watching = true;
+        self.watched_modified = self.scene_modified();
+    }
+
+    fn scene_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.scene_path).and_then(|m| m.modified()).ok()
+    }
+
+    fn step_world(&mut self) {
+        // Resuming after rewinding drops the snapshots that came after
+        self.timeline.branch();
+        if self.timeline.is_empty() {
+            let snapshot = self.snapshot();
+            self.timeline.push(snapshot);
+        }
+
+        // Constant timestep for physics is important
+        self.world.step(self.timestep);
+        self.step += 1;
+        if self.step % SNAPSHOT_INTERVAL == 0 {
+            let snapshot = self.snapshot();
+            self.timeline.push(snapshot);
+        }
+
+        self.refresh_constraints();
+    }
+
+    // Toolbar button for the current action, if it has one
+    fn tool(&self) -> Option<Tool> {
+        match self.current_action {
+            Action::None => Some(Tool::Grab),
+            Action::CreatingBall => Some(Tool::Ball),
+            Action::CreatingCuboid => Some(Tool::Box),
+            Action::DrawingPolygon => Some(Tool::Polygon),
+            Action::CreatingBallInSocket => Some(Tool::BallInSocket),
+            Action::CreatingFixedJoint => Some(Tool::FixedJoint),
+            Action::Erasing => Some(Tool::Delete),
+            Action::Inspecting => Some(Tool::Inspect),
+            _ => None,
+        }
+    }
+
+    fn action_name(&self) -> &'static str {
+        match self.current_action {
+            Action::CreatingBall => "ball",
+            Action::CreatingCuboid => "box",
+            Action::CreatingTriangle => "triangle",
+            Action::CreatingFixedJoint => "weld",
+            Action::CreatingBallInSocket => "hinge",
+            Action::Rotating => "rotate",
+            Action::Paste => "paste",
+            Action::BoxSelecting => "select",
+            Action::CreatingText => "text",
+            Action::ResizingText => "resize text",
+            Action::Erasing => "delete",
+            Action::DrawingPolygon => "polygon",
+            Action::Inspecting => "inspect",
+            Action::None => "grab",
+        }
+    }
+
+    fn check_scene_file(&mut self) {
+        let modified = self.scene_modified();
+        if modified.is_none() || modified == self.watched_modified {
+            return;
+        }
+
+        // A half written file fails to load, the write that finishes it changes the time again
+        self.watched_modified = modified;
+        match Scene::load(&self.scene_path) {
+            Ok(scene) => self.loads.push(Load::Reload { scene: scene }),
+            Err(e) => eprintln!("failed to reload {}: {}", self.scene_path.display(), e),
+        }
+    }
+
+    // Keeps the camera and whether the simulation runs
+    fn reload_scene(&mut self, scene: Scene) {
+        let position = self.camera.position();
+        let zoom = self.camera.zoom();
+        let paused = self.paused;
+
+        self.load_scene(scene);
+
+        self.camera.set_position(position);
+        self.camera.set_zoom(zoom);
+        self.paused = paused;
+        println!("reloaded {}", self.scene_path.display());
+    }
+
+    pub fn set_autosave_path(&mut self, path: PathBuf) {
+        self.autosave_path = Some(path);
+    }
+
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.bindings = bindings;
+    }
+
+    pub fn set_replaying(&mut self) {
+        self.replaying = true;
+    }
+
+    pub fn set_prefab_dir(&mut self, dir: PathBuf) {
+        self.prefab_dir = dir;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn set_timestep(&mut self, timestep: f64) {
+        self.timestep = timestep;
+    }
+
+    // Forgets what could be undone so far
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history = History::new(limit);
+    }
+
+    // Replaces everything in the world with `scene`
+    pub fn load_scene(&mut self, scene: Scene) {
+        self.clear_world();
+
+        self.gravity = na::Vector2::new(scene.gravity[0], scene.gravity[1]);
+        self.world.set_gravity(self.gravity);
+        self.camera.set_position(na::Vector2::new(scene.camera.position[0],
+                                                  scene.camera.position[1]));
+        self.camera.set_zoom(na::clamp(scene.camera.zoom, MIN_ZOOM, MAX_ZOOM));
+        self.paused = scene.paused;
+
+        let mut bodies = vec![];
+        for desc in &scene.world.bodies {
+            let id = self.new_body_id();
+            bodies.push(self.add_body(id, desc.color, desc.build()));
+        }
+
+        for desc in &scene.world.joints {
+            let body1 = desc.anchor1.body.and_then(|BodyIndex(i)| bodies.get(i).cloned());
+            let body2 = desc.anchor2.body.and_then(|BodyIndex(i)| bodies.get(i).cloned());
+            let joint = desc.build(&mut self.world, body1, body2);
+            let id = self.new_joint_id();
+            self.joints.insert(id, joint);
+        }
+
+        for label in scene.labels {
+            let body = label.body.and_then(|BodyIndex(i)| bodies.get(i).cloned());
+            let position = na::Point2::new(label.position[0], label.position[1]);
+            self.labels.push(Label::from_local(label.text, position, label.size, body));
+        }
+
+        self.refresh_constraints();
+    }
+
+    fn clear_world(&mut self) {
+        self.cancel_action();
+
+        self.world = World::new();
+        self.grabbed_object = None;
+        self.grabbed_object_joint = None;
+        self.joints.clear();
+        self.labels.clear();
+        self.selection.clear();
+        self.inspected = None;
+        self.history.clear();
+        self.step = 0;
+        self.timeline.clear();
+        self.constraints.clear();
+    }
+
+    fn autosave(&self) {
+        let path = match self.autosave_path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        // Crashing halfway through writing shouldn't lose the previous autosave
+        let temp = path.with_extension("tmp");
+        let saved = self.to_scene()
+            .save(&temp)
+            .and_then(|()| fs::rename(&temp, path).map_err(SceneError::from));
+        if let Err(e) = saved {
+            eprintln!("failed to autosave to {}: {}", path.display(), e);
+        }
+    }
+
+    fn save_scene_file(&mut self) {
+        if self.replaying {
+            println!("not saving {} during a replay", self.scene_path.display());
+            return;
+        }
+
+        match self.to_scene().save(&self.scene_path) {
+            Ok(()) => println!("saved scene to {}", self.scene_path.display()),
+            Err(e) => eprintln!("failed to save {}: {}", self.scene_path.display(), e),
+        }
+
+        // Our own saves aren't changes to reload
+        if self.watching {
+            self.watched_modified = self.scene_modified();
+        }
+    }
+
+    // The visible part of the world, or everything but planes when `whole_scene` is set
+    fn to_svg(&self, whole_scene: bool) -> Svg {
+        let mut mins = na::Point2::from_coordinates(self.camera.to_local(&na::zero()));
+        let mut maxs = na::Point2::from_coordinates(self.camera.to_local(&self.camera.size()));
+        if whole_scene {
+            let aabbs: Vec<AABB2<f64>> = self.world
+                .rigid_bodies()
+                .map(|b| b.borrow())
+                .filter(|rb| rb.shape().as_ref().as_shape::<Plane2<f64>>().is_none())
+                .map(|rb| rb.shape().aabb(rb.position()))
+                .collect();
+
+            if !aabbs.is_empty() {
+                // Leaves room for the borders
+                mins = na::Point2::new(aabbs.iter().fold(f64::MAX, |m, a| m.min(a.mins().x)),
+                                       aabbs.iter().fold(f64::MAX, |m, a| m.min(a.mins().y))) -
+                       na::Vector2::new(1.0, 1.0);
+                maxs = na::Point2::new(aabbs.iter().fold(f64::MIN, |m, a| m.max(a.maxs().x)),
+                                       aabbs.iter().fold(f64::MIN, |m, a| m.max(a.maxs().y))) +
+                       na::Vector2::new(1.0, 1.0);
+            }
+        }
+
+        let mut svg = Svg::new(mins, maxs, color::CORNFLOWER_BLUE);
+        for rb in self.world.rigid_bodies() {
+            let rb = rb.borrow();
+            svg.shape(rb.shape().as_ref(), rb.position(), rb.margin(), body_color(&rb));
+        }
+
+        // Debug lines are as wide as on screen, contact normals as long
+        let width = 6.0 / self.camera.zoom();
+        for constraint in &self.constraints {
+            match *constraint {
+                Constraint::RBRB(_, _, ref contact) => {
+                    let green = [0.0, 1.0, 0.0, 1.0];
+                    svg.line(&contact.world1, &contact.world2, green, width);
+
+                    let center = na::center(&contact.world1, &contact.world2);
+                    let normal = contact.normal / self.camera.zoom();
+                    svg.line(&center, &(center + normal * contact.depth), green, width);
+                    svg.line(&center, &(center + normal), green, width);
+                }
+                Constraint::BallInSocket(ref bis) => {
+                    let bis = bis.borrow();
+                    svg.line(&bis.anchor1_pos(), &bis.anchor2_pos(), [0.0, 0.0, 1.0, 1.0], width);
+                }
+                Constraint::Fixed(ref f) => {
+                    let f = f.borrow();
+                    let anchor1 = na::Point2::from_coordinates(f.anchor1_pos().translation.vector);
+                    let anchor2 = na::Point2::from_coordinates(f.anchor2_pos().translation.vector);
+                    svg.line(&anchor1, &anchor2, [1.0, 0.0, 0.0, 1.0], width);
+                }
+            }
+        }
+
+        for label in &self.labels {
+            svg.text(&label.world_position(), label.size, &label.text, color::BLACK);
+        }
+
+        svg
+    }
+
+    // Written next to the scene file
+    fn export_svg(&self, whole_scene: bool) {
+        let path = self.scene_path.with_extension("svg");
+        if self.replaying {
+            println!("not exporting {} during a replay", path.display());
+            return;
+        }
+
+        match self.to_svg(whole_scene).save(&path) {
+            Ok(()) => println!("exported {}", path.display()),
+            Err(e) => eprintln!("failed to export {}: {}", path.display(), e),
+        }
+    }
+
+    // The scene is loaded when it comes back from the app
+    fn open_scene_file(&mut self) {
+        if self.replaying {
+            return;
+        }
+
+        match Scene::load(&self.scene_path) {
+            Ok(scene) => self.loads.push(Load::Scene { scene: scene }),
+            Err(e) => eprintln!("failed to open {}: {}", self.scene_path.display(), e),
+        }
+    }
+
+    fn trans_camera(&mut self, dt: f64) {
+        let camera_move_speed = 100.0;
+
+        let mut delta: na::Vector2<f64> = na::zero();
+
+        if self.move_camera_up {
+            delta.y = -camera_move_speed;
+        } else if self.move_camera_down {
+            delta.y = camera_move_speed;
+        }
+
+        if self.move_camera_left {
+            delta.x = -camera_move_speed;
+        } else if self.move_camera_right {
+            delta.x = camera_move_speed;
+        }
+
+        delta.x += self.gamepad_axis(GamepadAxis::LeftX) * camera_move_speed;
+        delta.y += self.gamepad_axis(GamepadAxis::LeftY) * camera_move_speed;
+
+        delta *= dt;
+        self.camera.trans(&delta);
+    }
+
+    // Zero inside the deadzone
+    fn gamepad_axis(&self, axis: GamepadAxis) -> f64 {
+        let position = self.gamepad_axes[axis as usize];
+        if position.abs() < STICK_DEADZONE {
+            0.0
+        } else {
+            position
+        }
+    }
+
+    fn zoom_with_triggers(&mut self, dt: f64) {
+        let pull = self.gamepad_axis(GamepadAxis::RightTrigger) -
+                   self.gamepad_axis(GamepadAxis::LeftTrigger);
+        if pull != 0.0 {
+            let zoom = self.camera.zoom() * TRIGGER_ZOOM_RATE.powf(pull * dt);
+            self.camera.set_zoom(zoom.max(MIN_ZOOM).min(MAX_ZOOM));
+        }
+    }
+
+    // Moves the cursor like the mouse does, kept inside the window
+    fn move_gamepad_cursor(&mut self, dt: f64) {
+        let velocity = na::Vector2::new(self.gamepad_axis(GamepadAxis::RightX),
+                                        self.gamepad_axis(GamepadAxis::RightY)) *
+                       CURSOR_SPEED;
+        if velocity.x == 0.0 && velocity.y == 0.0 && !self.gamepad_cursor {
+            return;
+        }
+
+        // Also run while the stick is still, whatever is grabbed follows the camera
+        let size = self.camera.size();
+        let position = self.mouse_position + velocity * dt;
+        self.handle_mouse_move(position.x.max(0.0).min(size.x),
+                               position.y.max(0.0).min(size.y));
+        self.gamepad_cursor = true;
+    }
+
+    fn get_body_at_mouse(&self) -> Option<RigidBodyHandle<f64>> {
+        self.get_bodies_at_mouse().into_iter().next()
+    }
+
+    fn get_bodies_at_mouse(&self) -> Vec<RigidBodyHandle<f64>> {
+        let mut bodies = vec![];
+        for b in
+            self.world
+                .collision_world()
+                .interferences_with_point(&self.mouse_position_world, &CollisionGroups::new()) {
+            if let WorldObject::RigidBody(ref rb) = b.data {
+                bodies.push(rb.clone());
+            }
+        }
+
+        bodies
+    }
+
+    // Picks a body under the mouse other than `exclude`, falling back to the world (`None`)
+    fn get_other_body_at_mouse(&self,
+                               exclude: &RigidBodyHandle<f64>)
+                               -> Option<RigidBodyHandle<f64>> {
+        self.get_bodies_at_mouse().into_iter().find(|b| !Rc::ptr_eq(b, exclude))
+    }
+
+    fn is_selected(&self, body: &RigidBodyHandle<f64>) -> bool {
+        self.selection.iter().any(|b| Rc::ptr_eq(b, body))
+    }
+
+    fn toggle_selected(&mut self, body: RigidBodyHandle<f64>) {
+        if is_plane(&body) {
+            return;
+        }
+
+        match self.selection.iter().position(|b| Rc::ptr_eq(b, &body)) {
+            Some(i) => {
+                self.selection.remove(i);
+            }
+            None => self.selection.push(body),
+        }
+    }
+
+    fn select_in_box(&mut self) {
+        let mins = na::Point2::new(self.first_click_world.x.min(self.mouse_position_world.x),
+                                   self.first_click_world.y.min(self.mouse_position_world.y));
+        let maxs = na::Point2::new(self.first_click_world.x.max(self.mouse_position_world.x),
+                                   self.first_click_world.y.max(self.mouse_position_world.y));
+        let aabb = AABB2::new(mins, maxs);
+
+        let mut hits = vec![];
+        for b in self.world
+                .collision_world()
+                .interferences_with_aabb(&aabb, &CollisionGroups::new()) {
+            if let WorldObject::RigidBody(ref rb) = b.data {
+                // Planes have an infinite AABB and would always be hit
+                if !is_plane(rb) {
+                    hits.push(rb.clone());
+                }
+            }
+        }
+
+        if !self.ctrl_held {
+            self.selection.clear();
+        }
+
+        for rb in hits {
+            if !self.is_selected(&rb) {
+                self.selection.push(rb);
+            }
+        }
+    }
+
+    // Removes a body along with everything that references it
+    fn remove_body(&mut self, body: &RigidBodyHandle<f64>) {
+        let attached: Vec<JointId> = self.joints
+            .iter()
+            .filter(|&(_, j)| j.is_attached_to(body))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in attached {
+            if let Some(joint) = self.joints.remove(&id) {
+                joint.remove_from(&mut self.world);
+            }
+        }
+
+        if self.grabbed_object.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.release_grab();
+        }
+
+        if self.rotating_body.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.rotating_body = None;
+            self.current_action = Action::None;
+        }
+
+        if self.joint_body.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.joint_body = None;
+            self.action_step = 0;
+        }
+
+        // Removing labels shifts their indices
+        self.finish_editing_label();
+        if self.current_action == Action::ResizingText {
+            self.current_action = Action::CreatingText;
+        }
+        self.resizing_label = None;
+        self.labels.retain(|l| !l.is_attached_to(body));
+
+        self.selection.retain(|b| !Rc::ptr_eq(b, body));
+        self.world.remove_rigid_body(body);
+        self.refresh_constraints();
+    }
+
+    // Lets go of the grabbed body without recording the drag
+    fn release_grab(&mut self) {
+        if let Some(ref j) = self.grabbed_object_joint {
+            self.world.remove_fixed(j);
+        }
+
+        self.grabbed_object = None;
+        self.grabbed_object_joint = None;
+    }
+
+    fn copy_selection(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+
+        let mut blueprint = Blueprint::capture(&self.selection, self.joints.values());
+        let center = blueprint.center();
+        blueprint.translate(&-center);
+        self.clipboard = Some(blueprint);
+    }
+
+    fn cut_selection(&mut self) {
+        self.copy_selection();
+        let selection = mem::replace(&mut self.selection, vec![]);
+        self.delete_bodies(&selection);
+    }
+
+    fn start_paste(&mut self, blueprint: Blueprint) {
+        self.set_action(Action::Paste);
+        self.placing = Some(blueprint);
+    }
+
+    fn paste(&mut self) {
+        self.current_action = Action::None;
+        let mut blueprint = match self.placing.take() {
+            Some(blueprint) => blueprint,
+            None => return,
+        };
+
+        blueprint.translate(&self.mouse_position_world.coords);
+        let ids = self.spawn_blueprint(blueprint);
+        self.selection = ids.iter().filter_map(|&id| self.find_body(id)).collect();
+    }
+
+    // Merges the bodies and joints of a scene into this one, they follow the cursor until placed
+    fn import_prefab(&mut self, path: &Path, mut blueprint: Blueprint) {
+        blueprint.remove_planes();
+        if blueprint.bodies.is_empty() {
+            eprintln!("nothing to import in {}", path.display());
+            return;
+        }
+
+        let center = blueprint.center();
+        blueprint.translate(&-center);
+        self.start_paste(blueprint);
+        println!("placing {}", path.display());
+    }
+
+    // Each press moves on to the next scene in the prefab directory, which is imported when it
+    // comes back from the app
+    fn import_next_prefab(&mut self) {
+        if self.replaying {
+            return;
+        }
+
+        let entries = match fs::read_dir(&self.prefab_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("failed to open {}: {}", self.prefab_dir.display(), e);
+                return;
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map_or(false, |ext| ext == "json"))
+            .collect();
+        if paths.is_empty() {
+            eprintln!("no scenes in {}", self.prefab_dir.display());
+            return;
+        }
+
+        paths.sort();
+        let path = paths[self.prefab_index % paths.len()].clone();
+        self.prefab_index += 1;
+        match Scene::load(&path) {
+            Ok(scene) => {
+                self.loads.push(Load::Prefab {
+                                    path: path,
+                                    blueprint: scene.world,
+                                })
+            }
+            Err(e) => eprintln!("failed to import {}: {}", path.display(), e),
+        }
+    }
+
+    fn new_body_id(&mut self) -> BodyId {
+        let id = self.next_body_id;
+        self.next_body_id += 1;
+        id
+    }
+
+    fn new_joint_id(&mut self) -> JointId {
+        let id = self.next_joint_id;
+        self.next_joint_id += 1;
+        id
+    }
+
+    // Adds a body without recording it in the history
+    fn add_body(&mut self,
+                id: BodyId,
+                color: Color,
+                mut rb: RigidBody<f64>)
+                -> RigidBodyHandle<f64> {
+        set_body_data(&mut rb, id, color);
+        self.world.add_rigid_body(rb)
+    }
+
+    fn find_body(&self, id: BodyId) -> Option<RigidBodyHandle<f64>> {
+        self.world.rigid_bodies().find(|b| body_id(b) == Some(id)).cloned()
+    }
+
+    // `None` when the label was on a body that's gone
+    fn label_from_desc(&self, desc: &LabelDesc<BodyId>) -> Option<Label> {
+        let body = match desc.body.map(|id| self.find_body(id)) {
+            Some(None) => return None,
+            Some(body) => body,
+            None => None,
+        };
+        let position = na::Point2::new(desc.position[0], desc.position[1]);
+        Some(Label::from_local(desc.text.clone(), position, desc.size, body))
+    }
+
+    // Adds a body as an undoable edit
+    fn spawn_body(&mut self, rb: RigidBody<f64>) {
+        if let Some(desc) = BodyDesc::from_body(&rb) {
+            let id = self.new_body_id();
+            self.execute(Command::AddBody { id: id, desc: desc });
+        }
+    }
+
+    // Adds a joint as an undoable edit
+    fn spawn_joint(&mut self, desc: JointDesc<BodyId>) {
+        let id = self.new_joint_id();
+        self.execute(Command::AddJoint { id: id, desc: desc });
+    }
+
+    // Adds a whole group as a single undoable edit, returning the ids given to its bodies
+    fn spawn_blueprint(&mut self, blueprint: Blueprint) -> Vec<BodyId> {
+        let ids: Vec<BodyId> = blueprint.bodies.iter().map(|_| self.new_body_id()).collect();
+
+        let mut commands = vec![];
+        for (desc, &id) in blueprint.bodies.into_iter().zip(&ids) {
+            commands.push(Command::AddBody { id: id, desc: desc });
+        }
+
+        for desc in blueprint.joints {
+            let desc = desc.map_bodies(|&BodyIndex(i)| ids[i]);
+            let id = self.new_joint_id();
+            commands.push(Command::AddJoint { id: id, desc: desc });
+        }
+
+        self.execute(Command::Group(commands));
+        ids
+    }
+
+    // Removes bodies and the joints attached to them as a single undoable edit
+    fn delete_bodies(&mut self, bodies: &[RigidBodyHandle<f64>]) {
+        let mut commands = vec![];
+
+        // Joints go first so undoing brings the bodies back before the joints using them
+        for (&id, joint) in &self.joints {
+            if bodies.iter().any(|b| joint.is_attached_to(b)) {
+                if let Some(desc) = JointDesc::from_joint(joint, body_id) {
+                    commands.push(Command::RemoveJoint { id: id, desc: desc });
+                }
+            }
+        }
+
+        // Labels too, empty ones are dropped when they stop being edited anyway
+        for label in &self.labels {
+            if !label.text.is_empty() && bodies.iter().any(|b| label.is_attached_to(b)) {
+                commands.push(Command::RemoveLabel { desc: label_desc(label) });
+            }
+        }
+
+        for body in bodies {
+            let id = body_id(body);
+            let desc = BodyDesc::from_body(&body.borrow());
+            if let (Some(id), Some(desc)) = (id, desc) {
+                commands.push(Command::RemoveBody { id: id, desc: desc });
+            }
+        }
+
+        if !commands.is_empty() {
+            self.execute(Command::Group(commands));
+        }
+    }
+
+    fn delete_joint(&mut self, id: JointId) {
+        let desc = match self.joints.get(&id) {
+            Some(joint) => JointDesc::from_joint(joint, body_id),
+            None => return,
+        };
+
+        if let Some(desc) = desc {
+            self.execute(Command::RemoveJoint { id: id, desc: desc });
+        }
+    }
+
+    fn get_joint_at_mouse(&self) -> Option<JointId> {
+        for (&id, joint) in &self.joints {
+            let (anchor1, anchor2) = joint.anchor_positions();
+            let anchor1 = self.camera.to_window(&anchor1.coords);
+            let anchor2 = self.camera.to_window(&anchor2.coords);
+            if geometry::distance_to_segment(&self.mouse_position, &anchor1, &anchor2) <
+               JOINT_PICK_DISTANCE {
+                return Some(id);
+            }
+        }
+
+        None
+    }
+
+    // Deletes the selection, or the body under the mouse when nothing is selected
+    fn delete_selection(&mut self) {
+        let bodies = if self.selection.is_empty() {
+            self.get_body_at_mouse().into_iter().collect()
+        } else {
+            self.selection.clone()
+        };
+
+        self.delete_bodies(&bodies);
+    }
+
+    fn erase_at_mouse(&mut self) {
+        // The ground contains every point below it, erasing there shouldn't remove it
+        let body = self.get_bodies_at_mouse().into_iter().find(|b| !is_plane(b));
+        if let Some(id) = self.get_joint_at_mouse() {
+            self.delete_joint(id);
+        } else if let Some(body) = body {
+            self.delete_bodies(&[body]);
+        }
+    }
+
+    // Records an edit that was already made, like a drag with the mouse
+    fn record_transform(&mut self, body: &RigidBodyHandle<f64>) {
+        self.timeline.branch();
+        let after = body.borrow().position().clone();
+        if let Some(id) = body_id(body) {
+            if after != self.edit_start {
+                self.history.push(Command::SetTransform {
+                                      id: id,
+                                      before: self.edit_start,
+                                      after: after,
+                                  });
+            }
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        self.apply(&command);
+        self.history.push(command);
+    }
+
+    fn apply(&mut self, command: &Command) {
+        // Edits after rewinding replace what came after
+        self.timeline.branch();
+
+        match *command {
+            Command::AddBody { id, ref desc } => {
+                let rb = desc.build();
+                self.add_body(id, desc.color, rb);
+            }
+            Command::RemoveBody { id, .. } => {
+                if let Some(body) = self.find_body(id) {
+                    self.remove_body(&body);
+                }
+            }
+            Command::AddJoint { id, ref desc } => {
+                let body1 = desc.anchor1.body.and_then(|id| self.find_body(id));
+                let body2 = desc.anchor2.body.and_then(|id| self.find_body(id));
+                let joint = desc.build(&mut self.world, body1, body2);
+                self.joints.insert(id, joint);
+            }
+            Command::RemoveJoint { id, .. } => {
+                if let Some(joint) = self.joints.remove(&id) {
+                    joint.remove_from(&mut self.world);
+                }
+            }
+            Command::AddLabel { ref desc } => {
+                if let Some(label) = self.label_from_desc(desc) {
+                    self.labels.push(label);
+                }
+            }
+            Command::RemoveLabel { ref desc } => {
+                // Removing labels shifts their indices
+                self.finish_editing_label();
+                if self.current_action == Action::ResizingText {
+                    self.current_action = Action::CreatingText;
+                }
+                self.resizing_label = None;
+
+                if let Some(i) = self.labels.iter().position(|l| label_desc(l) == *desc) {
+                    self.labels.remove(i);
+                }
+            }
+            Command::SetTransform { id, after, .. } => {
+                if let Some(body) = self.find_body(id) {
+                    let mut rb = body.borrow_mut();
+                    rb.set_transformation(after);
+                    rb.set_lin_vel(na::zero());
+                    rb.set_ang_vel(na::zero());
+                    rb.activate(1.0);
+                }
+            }
+            Command::SetMotion { id, ref after, .. } => {
+                if let Some(body) = self.find_body(id) {
+                    set_motion(&mut body.borrow_mut(), after);
+                }
+            }
+            Command::SetMaterial { id, ref after, .. } => {
+                if let Some(body) = self.find_body(id) {
+                    self.set_material(id, &body, after);
+                }
+            }
+            Command::Group(ref commands) => {
+                for command in commands {
+                    self.apply(command);
+                }
+            }
+        }
+
+        self.refresh_constraints();
+    }
+
+    // Applies and records the command `edit` makes from the inspected body's id and properties
+    fn edit_inspected<F>(&mut self, edit: F)
+        where F: FnOnce(BodyId, Properties) -> Command
+    {
+        let id = match self.inspected {
+            Some(id) => id,
+            None => return,
+        };
+        let before = match self.find_body(id) {
+            Some(body) => Properties::from_body(&body.borrow()),
+            None => return,
+        };
+
+        let command = edit(id, before);
+        self.apply(&command);
+        self.history.push_merged(command);
+    }
+
+    fn set_material(&mut self, id: BodyId, body: &RigidBodyHandle<f64>, material: &Material) {
+        // The mass only comes from the shape when a body is made, so a new one is needed
+        let rebuild = {
+            let rb = body.borrow();
+            match material.density {
+                Some(density) => rb.is_static() || rb.density() != Some(density),
+                None => !rb.is_static(),
+            }
+        };
+        let desc = if rebuild {
+            BodyDesc::from_body(&body.borrow())
+        } else {
+            None
+        };
+        let body = match desc {
+            Some(mut desc) => {
+                desc.density = material.density;
+                self.rebuild_body(id, body, &desc)
+            }
+            None => body.clone(),
+        };
+
+        let mut rb = body.borrow_mut();
+        rb.set_restitution(material.restitution);
+        rb.set_friction(material.friction);
+        rb.set_margin(material.margin);
+        if !rb.is_static() {
+            rb.set_state(if material.kinematic {
+                             RigidBodyState::Kinematic
+                         } else {
+                             RigidBodyState::Dynamic
+                         });
+        }
+        set_body_data(&mut rb, id, material.color);
+        rb.activate(1.0);
+    }
+
+    // Replaces `body` with one built from `desc`. Its id, joints, labels, selection and the body a
+    // joint is being made from carry over
+    fn rebuild_body(&mut self,
+                    id: BodyId,
+                    body: &RigidBodyHandle<f64>,
+                    desc: &BodyDesc)
+                    -> RigidBodyHandle<f64> {
+        // The tool stays, only what holds on to the old body in the world lets go
+        if self.grabbed_object.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.release_grab();
+        }
+        if self.rotating_body.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.rotating_body = None;
+            self.action_step = 0;
+        }
+
+        let attached: Vec<(JointId, JointDesc<BodyId>)> = self.joints
+            .iter()
+            .filter(|&(_, j)| j.is_attached_to(body))
+            .filter_map(|(&i, j)| JointDesc::from_joint(j, body_id).map(|desc| (i, desc)))
+            .collect();
+        for &(joint_id, _) in &attached {
+            if let Some(joint) = self.joints.remove(&joint_id) {
+                joint.remove_from(&mut self.world);
+            }
+        }
+
+        self.world.remove_rigid_body(body);
+        let new_body = self.add_body(id, desc.color, desc.build());
+
+        for label in &mut self.labels {
+            label.reattach(body, &new_body);
+        }
+        for selected in &mut self.selection {
+            if Rc::ptr_eq(selected, body) {
+                *selected = new_body.clone();
+            }
+        }
+        if self.joint_body.as_ref().map_or(false, |b| Rc::ptr_eq(b, body)) {
+            self.joint_body = Some(new_body.clone());
+        }
+
+        for (joint_id, desc) in attached {
+            let body1 = desc.anchor1.body.and_then(|id| self.find_body(id));
+            let body2 = desc.anchor2.body.and_then(|id| self.find_body(id));
+            let joint = desc.build(&mut self.world, body1, body2);
+            self.joints.insert(joint_id, joint);
+        }
+
+        new_body
+    }
+
+    fn undo(&mut self) {
+        self.cancel_action();
+        if let Some(command) = self.history.undo() {
+            self.apply(&command);
+        }
+    }
+
+    fn redo(&mut self) {
+        self.cancel_action();
+        if let Some(command) = self.history.redo() {
+            self.apply(&command);
+        }
+    }
+
+    // Re-collects the constraints so joints created while paused are drawn right away
+    fn refresh_constraints(&mut self) {
+        self.constraints.clear();
+        self.world.constraints(&mut self.constraints);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let bodies = self.bodies()
+            .into_iter()
+            .filter_map(|(id, b)| BodyDesc::from_body(&b.borrow()).map(|desc| (id, desc)))
+            .collect();
+        let joints = self.joints
+            .iter()
+            .filter_map(|(&id, j)| JointDesc::from_joint(j, body_id).map(|desc| (id, desc)))
+            .collect();
+
+        Snapshot {
+            step: self.step,
+            bodies: bodies,
+            joints: joints,
+            labels: self.labels.iter().map(label_desc).collect(),
+            history: self.history.clone(),
+        }
+    }
+
+    // Puts the world back the way it was in `snapshot`, keeping the bodies that still exist
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        self.cancel_action();
+        self.release_grab();
+
+        let removed: Vec<JointId> = self.joints
+            .keys()
+            .filter(|&&id| !snapshot.joints.iter().any(|&(i, _)| i == id))
+            .cloned()
+            .collect();
+        for id in removed {
+            if let Some(joint) = self.joints.remove(&id) {
+                joint.remove_from(&mut self.world);
+            }
+        }
+
+        let mut bodies: BTreeMap<BodyId, RigidBodyHandle<f64>> =
+            self.bodies().into_iter().collect();
+        for (id, body) in mem::replace(&mut bodies, BTreeMap::new()) {
+            if snapshot.bodies.iter().any(|&(i, _)| i == id) {
+                bodies.insert(id, body);
+            } else {
+                self.remove_body(&body);
+            }
+        }
+
+        for &(id, ref desc) in &snapshot.bodies {
+            let existing = bodies.get(&id).cloned();
+            match existing {
+                Some(body) => {
+                    let unchanged = BodyDesc::from_body(&body.borrow())
+                        .map_or(false, |current| current.same_except_motion(desc));
+                    if unchanged {
+                        let mut rb = body.borrow_mut();
+                        desc.apply_motion(&mut rb);
+                        rb.activate(1.0);
+                    } else {
+                        // Edited in the inspector since, the old properties need a new body
+                        let body = self.rebuild_body(id, &body, desc);
+                        bodies.insert(id, body);
+                    }
+                }
+                None => {
+                    let body = self.add_body(id, desc.color, desc.build());
+                    bodies.insert(id, body);
+                }
+            }
+        }
+
+        for &(id, ref desc) in &snapshot.joints {
+            if !self.joints.contains_key(&id) {
+                let body1 = desc.anchor1.body.and_then(|id| bodies.get(&id).cloned());
+                let body2 = desc.anchor2.body.and_then(|id| bodies.get(&id).cloned());
+                let joint = desc.build(&mut self.world, body1, body2);
+                self.joints.insert(id, joint);
+            }
+        }
+
+        self.labels = snapshot.labels
+            .iter()
+            .filter_map(|desc| self.label_from_desc(desc))
+            .collect();
+        self.history = snapshot.history.clone();
+        self.step = snapshot.step;
+        self.refresh_constraints();
+    }
+
+    // Keeps the present reachable once the world is rewound
+    fn save_present(&mut self) {
+        if self.timeline.cursor().is_none() &&
+           self.timeline.last().map_or(false, |s| s.step != self.step) {
+            let snapshot = self.snapshot();
+            self.timeline.push(snapshot);
+        }
+    }
+
+    // Rewinds to the snapshot at `index`, pausing the simulation
+    fn seek_timeline(&mut self, index: usize) {
+        if self.timeline.cursor() == Some(index) {
+            return;
+        }
+
+        let snapshot = match self.timeline.seek(index) {
+            Some(snapshot) => snapshot.clone(),
+            None => return,
+        };
+        self.paused = true;
+        self.restore_snapshot(&snapshot);
+    }
+
+    // Moves `delta` snapshots back or forward from the current one
+    fn step_timeline(&mut self, delta: isize) {
+        self.save_present();
+        if self.timeline.is_empty() {
+            return;
+        }
+
+        let last = self.timeline.len() as isize - 1;
+        let current = self.timeline.cursor().map_or(last, |cursor| cursor as isize);
+        self.seek_timeline(na::clamp(current + delta, 0, last) as usize);
+    }
+
+    // Left, right and top edges of the timeline bar in window space
+    fn timeline_bar(&self) -> (f64, f64, f64) {
+        let size = self.camera.size();
+        (TIMELINE_MARGIN, size.x - TIMELINE_MARGIN, size.y - TIMELINE_MARGIN - TIMELINE_HEIGHT)
+    }
+
+    fn is_over_timeline(&self) -> bool {
+        let (left, right, top) = self.timeline_bar();
+        self.timeline.len() > 1 && self.mouse_position.x >= left &&
+        self.mouse_position.x <= right && self.mouse_position.y >= top &&
+        self.mouse_position.y <= top + TIMELINE_HEIGHT
+    }
+
+    fn scrub_to_mouse(&mut self) {
+        self.save_present();
+        let (left, right, _) = self.timeline_bar();
+        let t = na::clamp((self.mouse_position.x - left) / (right - left), 0.0, 1.0);
+        let last = self.timeline.len().saturating_sub(1);
+        self.seek_timeline((t * last as f64).round() as usize);
+    }
+
+    fn create_fixed_joint(&mut self, body1: RigidBodyHandle<f64>) {
+        self.current_action = Action::None;
+
+        let body2 = self.get_other_body_at_mouse(&body1);
+        let attach = na::Isometry2::new(self.mouse_position_world.coords, 0.0);
+        let attach1 = body1.borrow().position().inverse() * attach;
+        let attach2 = match body2 {
+            Some(ref b) => b.borrow().position().inverse() * attach,
+            None => attach,
+        };
+
+        let id1 = body_id(&body1);
+        let id2 = body2.as_ref().and_then(body_id);
+        self.spawn_joint(JointDesc {
+                             kind: JointKind::Fixed,
+                             anchor1: FrameDesc::from_isometry(id1, &attach1),
+                             anchor2: FrameDesc::from_isometry(id2, &attach2),
+                         });
+    }
+
+    fn is_near_first_polygon_point(&self) -> bool {
+        match self.polygon_points.first() {
+            Some(first) => {
+                let first = self.camera.to_window(&first.coords);
+                na::norm(&(first - self.mouse_position)) < POLYGON_CLOSE_DISTANCE
+            }
+            None => false,
+        }
+    }
+
+    fn create_polygon(&mut self) {
+        self.current_action = Action::None;
+        let points = mem::replace(&mut self.polygon_points, vec![]);
+        if points.len() < 3 {
+            return;
+        }
+
+        // The points are not guaranteed to be convex or in order, so take their hull
+        let hull: Vec<na::Point2<f64>> = transformation::convex_hull2_idx(&points)
+            .iter()
+            .map(|&i| points[i])
+            .collect();
+        if hull.len() < 3 || geometry::polygon_area(&hull) < MIN_POLYGON_AREA {
+            return;
+        }
+
+        let center = geometry::centroid(&hull);
+        let local_points = hull.iter()
+            .map(|p| na::Point2::from_coordinates(p.coords - center))
+            .collect();
+        let polygon = ConvexHull2::new(local_points);
+        let mut rb = RigidBody::new_dynamic(polygon,
+                                            DEFAULT_DENSITY,
+                                            DEFAULT_RESTITUTION,
+                                            DEFAULT_FRICTION);
+        rb.append_translation(&na::Translation2::new(center.x, center.y));
+        self.spawn_body(rb);
+    }
+
+    fn continue_drawing(&mut self) {
+        let far_enough = match self.polygon_points.last() {
+            Some(last) => {
+                let last = self.camera.to_window(&last.coords);
+                na::norm(&(last - self.mouse_position)) >= DRAW_MIN_SEGMENT
+            }
+            None => true,
+        };
+
+        if far_enough {
+            self.polygon_points.push(self.mouse_position_world);
+        }
+    }
+
+    // Turns the drawn outline into a compound body made of convex pieces
+    fn create_drawn_polygon(&mut self) {
+        self.current_action = Action::None;
+        self.action_step = 0;
+        let points = mem::replace(&mut self.polygon_points, vec![]);
+
+        let tolerance = DRAW_SIMPLIFY_TOLERANCE / self.camera.zoom();
+        let mut outline = geometry::simplify(&points, tolerance);
+        // The outline is closed, the last point would duplicate the first
+        if outline.len() > 1 && na::distance(&outline[0], &outline[outline.len() - 1]) < tolerance {
+            outline.pop();
+        }
+
+        if outline.len() < 3 || geometry::polygon_area(&outline) < MIN_POLYGON_AREA {
+            return;
+        }
+
+        let center = geometry::centroid(&outline);
+        let outline: Vec<na::Point2<f64>> = outline.iter()
+            .map(|p| na::Point2::from_coordinates(p.coords - center))
+            .collect();
+
+        // Self intersecting outlines can't be split up
+        let mut pieces = match geometry::convex_decomposition(&outline) {
+            Some(pieces) => pieces,
+            None => return,
+        };
+
+        let mut rb = if pieces.len() == 1 {
+            RigidBody::new_dynamic(ConvexHull2::new(pieces.remove(0)),
+                                   DEFAULT_DENSITY,
+                                   DEFAULT_RESTITUTION,
+                                   DEFAULT_FRICTION)
+        } else {
+            let shapes = pieces.into_iter()
+                .map(|piece| {
+                         (na::Isometry2::identity(), ShapeHandle2::new(ConvexHull2::new(piece)))
+                     })
+                .collect();
+            RigidBody::new_dynamic(Compound2::new(shapes),
+                                   DEFAULT_DENSITY,
+                                   DEFAULT_RESTITUTION,
+                                   DEFAULT_FRICTION)
+        };
+        rb.append_translation(&na::Translation2::new(center.x, center.y));
+        self.spawn_body(rb);
+    }
+
+    fn create_ball_in_socket(&mut self) {
+        let mut bodies = self.get_bodies_at_mouse().into_iter();
+        let body1 = match bodies.next() {
+            Some(b) => b,
+            None => return,
+        };
+        let body2 = bodies.next();
+
+        self.current_action = Action::None;
+
+        let attach = self.mouse_position_world;
+        let attach1 = body1.borrow().position().inverse() * attach;
+        let attach2 = match body2 {
+            Some(ref b) => b.borrow().position().inverse() * attach,
+            None => attach,
+        };
+
+        let id1 = body_id(&body1);
+        let id2 = body2.as_ref().and_then(body_id);
+        self.spawn_joint(JointDesc {
+                             kind: JointKind::BallInSocket,
+                             anchor1: FrameDesc::from_point(id1, &attach1),
+                             anchor2: FrameDesc::from_point(id2, &attach2),
+                         });
+    }
+
+    fn mouse_angle_around(&self, center: &na::Vector2<f64>) -> f64 {
+        let delta = self.mouse_position_world.coords - center;
+        delta.y.atan2(delta.x)
+    }
+
+    fn start_rotation(&mut self) {
+        let body = match self.get_body_at_mouse() {
+            Some(b) => b,
+            None => return,
+        };
+
+        let center = {
+            let mut rb = body.borrow_mut();
+            if rb.is_static() {
+                return;
+            }
+
+            // Keep the solver from fighting the edit
+            self.rotation_was_kinematic = is_kinematic(&rb);
+            rb.set_state(RigidBodyState::Kinematic);
+            rb.set_lin_vel(na::zero());
+            rb.set_ang_vel(na::zero());
+            self.rotation_start_angle = rb.position().rotation.angle();
+            self.edit_start = rb.position().clone();
+            rb.position().translation.vector
+        };
+
+        self.rotation_start_mouse_angle = self.mouse_angle_around(&center);
+        self.rotating_body = Some(body);
+        self.action_step += 1;
+    }
+
+    fn update_rotation(&mut self) {
+        let body = match self.rotating_body {
+            Some(ref b) => b.clone(),
+            None => return,
+        };
+
+        let mut rb = body.borrow_mut();
+        let center = rb.position().translation.vector;
+        let angle = self.rotation_start_angle + self.mouse_angle_around(&center) -
+                    self.rotation_start_mouse_angle;
+        let angle = if self.shift_held {
+            (angle / ROTATION_SNAP).round() * ROTATION_SNAP
+        } else {
+            angle
+        };
+
+        rb.set_transformation(na::Isometry2::new(center, angle));
+    }
+
+    fn finish_rotation(&mut self) {
+        if let Some(body) = self.rotating_body.take() {
+            {
+                let mut rb = body.borrow_mut();
+                if !self.rotation_was_kinematic {
+                    rb.set_state(RigidBodyState::Dynamic);
+                }
+                rb.activate(1.0);
             }
+
+            self.record_transform(&body);
         }
+    }
 
-        Game {
-            world: world,
-            camera: Camera::new(800, 600),
+    // Font size in pixels that makes `label` `label.size` world units tall
+    fn label_font_size(&self, label: &Label) -> u32 {
+        let size = (label.size * self.camera.zoom()).round() as u32;
+        if size < 1 { 1 } else { size }
+    }
 
-            paused: true,
+    fn with_glyphs<F, R>(&self, f: F) -> R
+        where F: FnOnce(&mut GlyphCache<'static>) -> R
+    {
+        let mut glyphs = self.glyphs.borrow_mut();
+        if glyphs.is_none() {
+            *glyphs = Some(GlyphCache::from_bytes(FONT).expect("invalid embedded font"));
+        }
 
-            grabbed_object: None,
-            grabbed_object_joint: None,
+        f(glyphs.as_mut().unwrap())
+    }
 
-            mouse_position: na::zero(),
-            mouse_position_world: na::Point2::new(0.0, 0.0),
-            first_click: na::zero(),
-            first_click_world: na::Point2::new(0.0, 0.0),
+    // Width of `label` in window space
+    fn label_width(&self, label: &Label) -> f64 {
+        let size = self.label_font_size(label);
+        self.with_glyphs(|glyphs| glyphs.width(size, &label.text))
+    }
 
-            current_action: Action::None,
-            action_step: 0,
+    // Position of the resize handle of `label` in window space
+    fn label_handle(&self, label: &Label) -> na::Vector2<f64> {
+        let position = self.camera.to_window(&label.world_position().coords);
+        na::Vector2::new(position.x + self.label_width(label), position.y)
+    }
 
-            move_camera_up: false,
-            move_camera_down: false,
-            move_camera_left: false,
-            move_camera_right: false,
+    fn get_label_at_mouse(&self) -> Option<usize> {
+        self.labels.iter().position(|label| {
+            let position = self.camera.to_window(&label.world_position().coords);
+            let height = label.size * self.camera.zoom();
+            let width = self.label_width(label);
 
-            constraints: vec![],
+            self.mouse_position.x >= position.x && self.mouse_position.x <= position.x + width &&
+            self.mouse_position.y >= position.y - height && self.mouse_position.y <= position.y
+        })
+    }
+
+    fn get_label_handle_at_mouse(&self) -> Option<usize> {
+        self.labels.iter().position(|label| {
+            let handle = self.label_handle(label);
+            na::abs(&(handle.x - self.mouse_position.x)) <= TEXT_HANDLE_SIZE / 2.0 &&
+            na::abs(&(handle.y - self.mouse_position.y)) <= TEXT_HANDLE_SIZE / 2.0
+        })
+    }
+
+    fn start_label(&mut self) {
+        self.finish_editing_label();
+
+        if let Some(i) = self.get_label_handle_at_mouse() {
+            self.current_action = Action::ResizingText;
+            self.resizing_label = Some(i);
+            self.resize_start_size = self.labels[i].size;
+            self.resize_start_width = self.label_width(&self.labels[i]);
+            self.first_click = self.mouse_position;
+        } else if let Some(i) = self.get_label_at_mouse() {
+            self.editing_label = Some(i);
+        } else {
+            let body = self.get_body_at_mouse();
+            self.labels.push(Label::new(self.mouse_position_world, DEFAULT_TEXT_SIZE, body));
+            self.editing_label = Some(self.labels.len() - 1);
         }
     }
 
-    fn trans_camera(&mut self, dt: f64) {
-        let camera_move_speed = 100.0;
+    fn update_label_size(&mut self) {
+        let i = match self.resizing_label {
+            Some(i) => i,
+            None => return,
+        };
+
+        // Scale the text so its end follows the handle
+        let start = self.first_click.x - self.resize_start_width;
+        let width = self.mouse_position.x - start;
+        let size = if self.resize_start_width > 0.0 {
+            self.resize_start_size * width / self.resize_start_width
+        } else {
+            self.resize_start_size
+        };
 
-        let mut delta: na::Vector2<f64> = na::zero();
+        self.labels[i].size = na::clamp(size, MIN_TEXT_SIZE, MAX_TEXT_SIZE);
+    }
 
-        if self.move_camera_up {
-            delta.y = -camera_move_speed;
-        } else if self.move_camera_down {
-            delta.y = camera_move_speed;
+    // Stops typing into the current label, dropping it if it was left empty
+    fn finish_editing_label(&mut self) {
+        if let Some(i) = self.editing_label.take() {
+            if self.labels[i].text.is_empty() {
+                self.labels.remove(i);
+            }
         }
+    }
 
-        if self.move_camera_left {
-            delta.x = -camera_move_speed;
-        } else if self.move_camera_right {
-            delta.x = camera_move_speed;
+    fn set_action(&mut self, action: Action) {
+        self.cancel_action();
+        self.current_action = action;
+    }
+
+    fn cancel_action(&mut self) {
+        self.finish_rotation();
+        self.finish_editing_label();
+        self.resizing_label = None;
+        self.current_action = Action::None;
+        self.action_step = 0;
+        self.polygon_points.clear();
+        self.joint_body = None;
+        self.placing = None;
+    }
+
+    // What the left mouse button does by default, depends on the current action
+    fn use_tool(&mut self, pressed: bool) {
+        if self.scrubbing || pressed && self.is_over_timeline() {
+            self.scrubbing = pressed;
+            if pressed {
+                self.scrub_to_mouse();
+            }
+            return;
         }
 
-        delta *= dt;
-        self.camera.trans(&delta);
+        if self.current_action == Action::None {
+            if pressed && self.shift_held {
+                self.current_action = Action::BoxSelecting;
+                self.first_click = self.mouse_position;
+                self.first_click_world = self.mouse_position_world;
+            } else if pressed && self.ctrl_held {
+                if let Some(b) = self.get_body_at_mouse() {
+                    self.toggle_selected(b);
+                }
+            } else if pressed {
+                self.grabbed_object = self.get_body_at_mouse();
+
+                // The ground can be dragged around but copying it would make a second one
+                match self.grabbed_object {
+                    Some(ref b) if self.is_selected(b) => {}
+                    Some(ref b) if is_plane(b) => self.selection.clear(),
+                    Some(ref b) => self.selection = vec![b.clone()],
+                    None => self.selection.clear(),
+                }
+
+                if let Some(ref b) = self.grabbed_object {
+                    if let Some(ref j) = self.grabbed_object_joint {
+                        self.world.remove_fixed(j);
+                    }
+
+                    let attach2 = na::Isometry2::new(self.mouse_position_world.coords, 0.0);
+                    let attach1 = b.borrow().position().inverse() * attach2;
+                    let anchor1 = Anchor::new(Some(b.clone()), attach1);
+                    let anchor2 = Anchor::new(None, attach2);
+                    let joint = Fixed::new(anchor1, anchor2);
+                    self.grabbed_object_joint = Some(self.world.add_fixed(joint));
+                    self.edit_start = b.borrow().position().clone();
+                }
+            } else {
+                if let Some(ref j) = self.grabbed_object_joint {
+                    self.world.remove_fixed(j);
+                }
+
+                if let Some(b) = self.grabbed_object.take() {
+                    self.record_transform(&b);
+                }
+                self.grabbed_object_joint = None;
+            }
+        } else if self.current_action == Action::CreatingText && pressed {
+            self.start_label();
+        } else if self.current_action == Action::ResizingText && !pressed {
+            self.current_action = Action::CreatingText;
+            self.resizing_label = None;
+        } else if self.current_action == Action::DrawingPolygon {
+            if pressed && self.action_step == 0 {
+                self.polygon_points = vec![self.mouse_position_world];
+                self.action_step += 1;
+            } else if !pressed && self.action_step == 1 {
+                self.create_drawn_polygon();
+            }
+        } else if self.current_action == Action::Erasing && pressed {
+            self.erase_at_mouse();
+        } else if self.current_action == Action::Inspecting && pressed {
+            self.inspected = self.get_body_at_mouse().and_then(|b| body_id(&b));
+        } else if self.current_action == Action::Paste && pressed {
+            self.paste();
+        } else if self.current_action == Action::BoxSelecting && !pressed {
+            self.current_action = Action::None;
+            self.select_in_box();
+        } else if self.current_action == Action::CreatingBall {
+            if pressed && self.action_step == 0 {
+                self.first_click = self.mouse_position;
+                self.first_click_world = self.mouse_position_world;
+                self.action_step += 1;
+            } else if !pressed && self.action_step == 1 {
+                let radius = na::distance(&self.first_click_world, &self.mouse_position_world);
+                let radius = na::clamp(radius, MIN_BALL_RADIUS, MAX_BALL_RADIUS);
+                if radius > 0.0 {
+                    self.current_action = Action::None;
+
+                    let ball = Ball2::new(radius);
+                    let mut rb = RigidBody::new_dynamic(ball,
+                                                        DEFAULT_DENSITY,
+                                                        DEFAULT_RESTITUTION,
+                                                        DEFAULT_FRICTION);
+                    rb.append_translation(&na::Translation2::new(self.first_click_world.x,
+                                                                 self.first_click_world.y));
+                    self.spawn_body(rb);
+                }
+            }
+        } else if self.current_action == Action::CreatingCuboid {
+            if pressed && self.action_step == 0 {
+                self.first_click = self.mouse_position;
+                self.first_click_world = self.mouse_position_world;
+                self.action_step += 1;
+            } else if !pressed && self.action_step == 1 &&
+                      self.mouse_position != self.first_click {
+                self.current_action = Action::None;
+
+                let width = self.mouse_position_world.x - self.first_click_world.x;
+                let width = na::abs(&width);
+                let width = na::clamp(width, MIN_CUBOID_WIDTH, MAX_CUBOID_WIDTH);
+
+                let height = self.mouse_position_world.y - self.first_click_world.y;
+                let height = na::abs(&height);
+                let height = na::clamp(height, MIN_CUBOID_HEIGHT, MAX_CUBOID_HEIGHT);
+
+                let cuboid = Cuboid2::new(na::Vector2::new(width, height));
+                let mut rb = RigidBody::new_dynamic(cuboid,
+                                                    DEFAULT_DENSITY,
+                                                    DEFAULT_RESTITUTION,
+                                                    DEFAULT_FRICTION);
+                rb.append_translation(&na::Translation2::new(self.first_click_world.x,
+                                                             self.first_click_world.y));
+                self.spawn_body(rb);
+            }
+        } else if self.current_action == Action::CreatingTriangle && pressed {
+            if self.polygon_points.len() >= 3 && self.is_near_first_polygon_point() {
+                self.create_polygon();
+            } else if self.polygon_points.len() < MAX_POLYGON_VERTICES {
+                self.polygon_points.push(self.mouse_position_world);
+            }
+        } else if self.current_action == Action::CreatingFixedJoint && pressed {
+            if self.action_step == 0 {
+                self.joint_body = self.get_body_at_mouse();
+                if self.joint_body.is_some() {
+                    self.first_click = self.mouse_position;
+                    self.first_click_world = self.mouse_position_world;
+                    self.action_step += 1;
+                }
+            } else if let Some(body1) = self.joint_body.take() {
+                self.create_fixed_joint(body1);
+            }
+        } else if self.current_action == Action::CreatingBallInSocket && pressed {
+            self.create_ball_in_socket();
+        } else if self.current_action == Action::Rotating {
+            if pressed && self.action_step == 0 {
+                self.start_rotation();
+            } else if !pressed && self.action_step == 1 {
+                self.current_action = Action::None;
+                self.finish_rotation();
+            }
+        }
     }
 
-    fn get_body_at_mouse(&self) -> Option<RigidBodyHandle<f64>> {
-        for b in
-            self.world
-                .collision_world()
-                .interferences_with_point(&self.mouse_position_world, &CollisionGroups::new()) {
-            if let WorldObject::RigidBody(ref rb) = b.data {
-                return Some(rb.clone());
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl_held,
+            shift: self.shift_held,
+            alt: self.alt_held,
+        }
+    }
+
+    fn handle_trigger(&mut self, trigger: Trigger, pressed: bool) {
+        if pressed {
+            let modifiers = self.modifiers();
+            if let Some(control) = self.bindings.pressed(trigger, &modifiers) {
+                self.control(control, true);
+            }
+        } else {
+            for control in self.bindings.released(trigger) {
+                self.control(control, false);
             }
         }
+    }
 
-        None
+    fn control(&mut self, control: Control, pressed: bool) {
+        match control {
+            Control::PanUp => self.move_camera_up = pressed,
+            Control::PanDown => self.move_camera_down = pressed,
+            Control::PanLeft => self.move_camera_left = pressed,
+            Control::PanRight => self.move_camera_right = pressed,
+            Control::UseTool => self.use_tool(pressed),
+
+            // Everything else happens once, on press
+            _ if !pressed => (),
+
+            Control::ZoomIn => self.zoom_in(),
+            Control::ZoomOut => self.zoom_out(),
+            Control::Pause => self.paused = !self.paused,
+            Control::StepBack => self.step_timeline(-1),
+            Control::StepForward => self.step_timeline(1),
+
+            Control::Ball => self.set_action(Action::CreatingBall),
+            Control::Box => self.set_action(Action::CreatingCuboid),
+            Control::Polygon => self.set_action(Action::CreatingTriangle),
+            Control::Hinge => self.set_action(Action::CreatingBallInSocket),
+            Control::Weld => self.set_action(Action::CreatingFixedJoint),
+            Control::Rotate => self.set_action(Action::Rotating),
+            Control::Text => self.set_action(Action::CreatingText),
+            Control::Erase => self.set_action(Action::Erasing),
+            Control::Pencil => self.set_action(Action::DrawingPolygon),
+            Control::Inspect => self.set_action(Action::Inspecting),
+
+            Control::Delete => self.delete_selection(),
+            Control::Undo => self.undo(),
+            Control::Redo => self.redo(),
+            Control::Save => self.save_scene_file(),
+            Control::Open => self.open_scene_file(),
+            Control::Copy => self.copy_selection(),
+            Control::Cut => self.cut_selection(),
+            Control::Paste => {
+                if let Some(clipboard) = self.clipboard.clone() {
+                    self.start_paste(clipboard);
+                }
+            }
+            Control::Import => self.import_next_prefab(),
+            Control::ExportSvg => self.export_svg(false),
+            Control::ExportSvgScene => self.export_svg(true),
+
+            Control::Confirm => {
+                if self.current_action == Action::CreatingTriangle {
+                    self.create_polygon();
+                }
+            }
+            // Nothing to cancel, so it pauses the whole screen instead
+            Control::Cancel => {
+                if self.current_action == Action::None && self.grabbed_object.is_none() {
+                    self.transition = Transition::Push(Box::new(Pause::new()));
+                } else {
+                    self.cancel_action();
+                }
+            }
+        }
     }
 
     fn zoom_in(&mut self) {
@@ -185,21 +2044,12 @@ impl Game {
     }
 }
 
-impl State for Game {
-    fn update(&mut self, dt: f64) {
-        // Constant timestep for physics is important
-        let timestep = 1.0 / 60.0;
-        if !self.paused {
-            self.world.step(timestep);
-
-            self.constraints.clear();
-            self.world.constraints(&mut self.constraints);
-        }
-
-        self.trans_camera(dt);
-    }
-
-    fn render(&self, c: &Context, g: &mut GlGraphics) {
+impl Game {
+    // Shared by the window and the software rasterizer, `glyphs` has to match the backend
+    fn draw<G, C>(&self, c: &Context, g: &mut G, glyphs: &mut C)
+        where G: Graphics,
+              C: CharacterCache<Texture = G::Texture>
+    {
         for rb in self.world.rigid_bodies() {
             let object = WorldObject::RigidBody(rb.clone());
             let bobject = object.borrow();
@@ -213,13 +2063,9 @@ impl State for Game {
                 .rot_rad(rotation)
                 .zoom(self.camera.zoom());
 
-            if let Some(s) = shape.as_shape::<Ball2<f64>>() {
-                let radius = s.radius() + margin;
-                view::draw_ball(radius, [1.0; 4], &c, g);
-            } else if let Some(s) = shape.as_shape::<Cuboid2<f64>>() {
-                let width = s.half_extents().x + margin;
-                let height = s.half_extents().y + margin;
-                view::draw_cuboid(width, height, [1.0; 4], &c, g);
+            view::draw_shape(shape, margin, body_color(&rb.borrow()), &c, g);
+            if self.is_selected(rb) {
+                view::draw_shape_outline(shape, margin, color::SELECTION, &c, g);
             }
         }
 
@@ -292,6 +2138,43 @@ impl State for Game {
             }
         }
 
+        for (i, label) in self.labels.iter().enumerate() {
+            let position = self.camera.to_window(&label.world_position().coords);
+            let size = self.label_font_size(label);
+            graphics::Text::new_color(color::BLACK, size).draw(&label.text,
+                                                               glyphs,
+                                                               &c.draw_state,
+                                                               c.trans(position.x, position.y)
+                                                                   .transform,
+                                                               g);
+            // Measured with `glyphs`, `label_width` would borrow the cache being drawn with
+            let width = glyphs.width(size, &label.text);
+
+            if self.editing_label == Some(i) {
+                let x = position.x + width;
+                graphics::Line::new(color::BLACK, 0.5)
+                    .draw([x, position.y - size as f64, x, position.y],
+                          &c.draw_state,
+                          c.transform,
+                          g);
+            }
+
+            if self.current_action == Action::CreatingText ||
+               self.current_action == Action::ResizingText {
+                let handle = na::Vector2::new(position.x + width, position.y);
+                let half = TEXT_HANDLE_SIZE / 2.0;
+                graphics::Rectangle::new(color::WHITE)
+                    .border(graphics::rectangle::Border {
+                                color: color::BLACK,
+                                radius: 0.5,
+                            })
+                    .draw([handle.x - half, handle.y - half, TEXT_HANDLE_SIZE, TEXT_HANDLE_SIZE],
+                          &c.draw_state,
+                          c.transform,
+                          g);
+            }
+        }
+
         if self.current_action != Action::None {
             match self.current_action {
                 Action::CreatingBall if self.action_step == 1 => {
@@ -356,6 +2239,132 @@ impl State for Game {
                                                                 g);
                 }
 
+                Action::CreatingTriangle if !self.polygon_points.is_empty() => {
+                    let line = graphics::Line::new(color::WHITE, 1.0);
+                    let points: Vec<na::Vector2<f64>> = self.polygon_points
+                        .iter()
+                        .map(|p| self.camera.to_window(&p.coords))
+                        .collect();
+
+                    for pair in points.windows(2) {
+                        line.draw([pair[0].x, pair[0].y, pair[1].x, pair[1].y],
+                                  &c.draw_state,
+                                  c.transform,
+                                  g);
+                    }
+
+                    let first = points[0];
+                    let last = points[points.len() - 1];
+                    line.draw([last.x, last.y, self.mouse_position.x, self.mouse_position.y],
+                              &c.draw_state,
+                              c.transform,
+                              g);
+
+                    // Show where the shape will close
+                    graphics::Line::new(color::WHITE.shade(0.5), 0.5)
+                        .draw([self.mouse_position.x, self.mouse_position.y, first.x, first.y],
+                              &c.draw_state,
+                              c.transform,
+                              g);
+
+                    if points.len() >= 3 {
+                        let radius = POLYGON_CLOSE_DISTANCE;
+                        let dradius = radius * 2.0;
+                        graphics::Ellipse::new_border(color::WHITE, 0.5)
+                            .resolution(16)
+                            .draw([-radius, -radius, dradius, dradius],
+                                  &c.draw_state,
+                                  c.trans(first.x, first.y).transform,
+                                  g);
+                    }
+                }
+
+                Action::DrawingPolygon if self.action_step == 1 => {
+                    let line = graphics::Line::new(color::WHITE, 1.0);
+                    let points: Vec<na::Vector2<f64>> = self.polygon_points
+                        .iter()
+                        .map(|p| self.camera.to_window(&p.coords))
+                        .collect();
+
+                    for pair in points.windows(2) {
+                        line.draw([pair[0].x, pair[0].y, pair[1].x, pair[1].y],
+                                  &c.draw_state,
+                                  c.transform,
+                                  g);
+                    }
+                }
+
+                Action::CreatingFixedJoint if self.action_step == 1 => {
+                    graphics::Line::new([1.0, 0.0, 0.0, 1.0], 1.0).draw([self.first_click.x,
+                                                                         self.first_click.y,
+                                                                         self.mouse_position.x,
+                                                                         self.mouse_position.y],
+                                                                        &c.draw_state,
+                                                                        c.transform,
+                                                                        g);
+                }
+
+                Action::BoxSelecting => {
+                    let x = self.first_click.x.min(self.mouse_position.x);
+                    let y = self.first_click.y.min(self.mouse_position.y);
+                    let width = (self.first_click.x - self.mouse_position.x).abs();
+                    let height = (self.first_click.y - self.mouse_position.y).abs();
+
+                    let mut fill = color::SELECTION;
+                    fill[3] = 0.2;
+                    graphics::Rectangle::new(fill)
+                        .border(graphics::rectangle::Border {
+                                    color: color::SELECTION,
+                                    radius: 0.5,
+                                })
+                        .draw([x, y, width, height], &c.draw_state, c.transform, g);
+                }
+
+                Action::Paste => {
+                    if let Some(ref placing) = self.placing {
+                        let mut preview = color::SELECTION;
+                        preview[3] = 0.5;
+
+                        for body in &placing.bodies {
+                            let position = self.mouse_position_world.coords +
+                                           na::Vector2::new(body.position[0], body.position[1]);
+                            let position = self.camera.to_window(&position);
+                            let c = c.trans(position.x, position.y)
+                                .rot_rad(body.rotation.angle())
+                                .zoom(self.camera.zoom());
+                            view::draw_shape_desc(&body.shape, preview, &c, g);
+                        }
+                    }
+                }
+
+                Action::Rotating if self.action_step == 1 => {
+                    if let Some(ref body) = self.rotating_body {
+                        let center = body.borrow().position().translation.vector;
+                        let center = self.camera.to_window(&center);
+
+                        graphics::Line::new(color::WHITE, 1.0).draw([center.x,
+                                                                     center.y,
+                                                                     self.mouse_position.x,
+                                                                     self.mouse_position.y],
+                                                                    &c.draw_state,
+                                                                    c.transform,
+                                                                    g);
+                    }
+                }
+
+                Action::Erasing => {
+                    let radius = JOINT_PICK_DISTANCE;
+                    let dradius = radius * 2.0;
+
+                    graphics::Ellipse::new_border([1.0, 0.0, 0.0, 1.0], 1.0)
+                        .resolution(16)
+                        .draw([-radius, -radius, dradius, dradius],
+                              &c.draw_state,
+                              c.trans(self.mouse_position.x, self.mouse_position.y)
+                                  .transform,
+                              g);
+                }
+
                 Action::CreatingBallInSocket => {
                     let radius = 5.0;
                     let dradius = radius * 2.0;
@@ -384,91 +2393,116 @@ impl State for Game {
                 _ => {}
             }
         }
+
+        if self.timeline.len() > 1 {
+            let (left, right, top) = self.timeline_bar();
+            graphics::Rectangle::new([0.0, 0.0, 0.0, 0.3])
+                .draw([left, top, right - left, TIMELINE_HEIGHT],
+                      &c.draw_state,
+                      c.transform,
+                      g);
+
+            // Right end while live
+            let last = self.timeline.len() - 1;
+            let t = self.timeline.cursor().unwrap_or(last) as f64 / last as f64;
+            let x = left + t * (right - left);
+            let color = if self.timeline.cursor().is_some() {
+                color::SELECTION
+            } else {
+                color::WHITE
+            };
+            graphics::Rectangle::new(color)
+                .draw([x - 2.0, top, 4.0, TIMELINE_HEIGHT], &c.draw_state, c.transform, g);
+        }
+
+        if self.gamepad_cursor {
+            let radius = 6.0;
+            let dradius = radius * 2.0;
+
+            graphics::Ellipse::new(color::WHITE)
+                .border(graphics::ellipse::Border {
+                            color: color::BLACK,
+                            radius: 1.0,
+                        })
+                .resolution(16)
+                .draw([-radius, -radius, dradius, dradius],
+                      &c.draw_state,
+                      c.trans(self.mouse_position.x, self.mouse_position.y).transform,
+                      g);
+        }
+    }
+
+}
+
+impl State for Game {
+    fn update(&mut self, dt: f64) {
+        if !self.paused {
+            self.step_world();
+        }
+
+        self.trans_camera(dt);
+        self.zoom_with_triggers(dt);
+        self.move_gamepad_cursor(dt);
+
+        self.autosave_timer += dt;
+        if self.autosave_timer >= AUTOSAVE_INTERVAL {
+            self.autosave_timer = 0.0;
+            self.autosave();
+        }
+
+        if self.watching {
+            self.watch_timer += dt;
+            if self.watch_timer >= WATCH_INTERVAL {
+                self.watch_timer = 0.0;
+                self.check_scene_file();
+            }
+        }
+    }
+
+    fn render(&self, c: &Context, g: &mut GlGraphics) {
+        self.with_glyphs(|glyphs| self.draw(c, g, glyphs));
+    }
+
+    fn render_software(&self, c: &Context, canvas: &mut Canvas) {
+        let mut glyphs = self.software_glyphs.borrow_mut();
+        if glyphs.is_none() {
+            *glyphs = Some(raster::GlyphCache::from_bytes(FONT).expect("invalid embedded font"));
+        }
+
+        self.draw(c, canvas, glyphs.as_mut().unwrap());
     }
 
     fn handle_mouse_move(&mut self, x: f64, y: f64) {
+        // The mouse takes the cursor back
+        self.gamepad_cursor = false;
         self.mouse_position.x = x;
         self.mouse_position.y = y;
         let mapped_coords = self.camera.to_local(&self.mouse_position);
         self.mouse_position_world.x = mapped_coords.x;
         self.mouse_position_world.y = mapped_coords.y;
 
+        if self.scrubbing {
+            self.scrub_to_mouse();
+            return;
+        }
+
         let attach2 = na::Isometry2::new(self.mouse_position_world.coords, 0.0);
         if self.grabbed_object.is_some() {
             let joint = self.grabbed_object_joint.as_ref().unwrap();
             joint.borrow_mut().set_local2(attach2);
         }
+
+        if self.current_action == Action::Rotating {
+            self.update_rotation();
+        } else if self.current_action == Action::DrawingPolygon && self.action_step == 1 {
+            self.continue_drawing();
+        } else if self.current_action == Action::ResizingText {
+            self.update_label_size();
+        }
     }
 
     fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
-        if button == MouseButton::Left {
-            if self.current_action == Action::None {
-                if pressed {
-                    self.grabbed_object = self.get_body_at_mouse();
-
-                    if let Some(ref b) = self.grabbed_object {
-                        if let Some(ref j) = self.grabbed_object_joint {
-                            self.world.remove_fixed(j);
-                        }
-
-                        let attach2 = na::Isometry2::new(self.mouse_position_world.coords, 0.0);
-                        let attach1 = b.borrow().position().inverse() * attach2;
-                        let anchor1 = Anchor::new(Some(b.clone()), attach1);
-                        let anchor2 = Anchor::new(None, attach2);
-                        let joint = Fixed::new(anchor1, anchor2);
-                        self.grabbed_object_joint = Some(self.world.add_fixed(joint));
-                    }
-                } else {
-                    if let Some(ref j) = self.grabbed_object_joint {
-                        self.world.remove_fixed(j);
-                    }
-
-                    self.grabbed_object = None;
-                    self.grabbed_object_joint = None;
-                }
-            } else if self.current_action == Action::CreatingBall {
-                if pressed && self.action_step == 0 {
-                    self.first_click = self.mouse_position;
-                    self.first_click_world = self.mouse_position_world;
-                    self.action_step += 1;
-                } else if !pressed && self.action_step == 1 {
-                    let radius = na::distance(&self.first_click_world, &self.mouse_position_world);
-                    let radius = na::clamp(radius, MIN_BALL_RADIUS, MAX_BALL_RADIUS);
-                    if radius > 0.0 {
-                        self.current_action = Action::None;
-
-                        let ball = Ball2::new(radius);
-                        let mut rb = RigidBody::new_dynamic(ball, 1.0, 0.3, 0.6);
-                        rb.append_translation(&na::Translation2::new(self.first_click_world.x,
-                                                                     self.first_click_world.y));
-                        self.world.add_rigid_body(rb);
-                    }
-                }
-            } else if self.current_action == Action::CreatingCuboid {
-                if pressed && self.action_step == 0 {
-                    self.first_click = self.mouse_position;
-                    self.first_click_world = self.mouse_position_world;
-                    self.action_step += 1;
-                } else if !pressed && self.action_step == 1 &&
-                          self.mouse_position != self.first_click {
-                    self.current_action = Action::None;
-
-                    let width = self.mouse_position_world.x - self.first_click_world.x;
-                    let width = na::abs(&width);
-                    let width = na::clamp(width, MIN_CUBOID_WIDTH, MAX_CUBOID_WIDTH);
-
-                    let height = self.mouse_position_world.y - self.first_click_world.y;
-                    let height = na::abs(&height);
-                    let height = na::clamp(height, MIN_CUBOID_HEIGHT, MAX_CUBOID_HEIGHT);
-
-                    let cuboid = Cuboid2::new(na::Vector2::new(width, height));
-                    let mut rb = RigidBody::new_dynamic(cuboid, 1.0, 0.3, 0.6);
-                    rb.append_translation(&na::Translation2::new(self.first_click_world.x,
-                                                                 self.first_click_world.y));
-                    self.world.add_rigid_body(rb);
-                }
-            }
-        }
+        self.handle_trigger(Trigger::Mouse(button), pressed);
     }
 
     fn handle_mouse_scroll(&mut self, _: f64, y: f64) {
@@ -482,34 +2516,54 @@ impl State for Game {
     }
 
     fn handle_key(&mut self, key: Key, pressed: bool) {
-        match key {
-            Key::Up => self.move_camera_up = pressed,
-            Key::Down => self.move_camera_down = pressed,
-            Key::Left => self.move_camera_left = pressed,
-            Key::Right => self.move_camera_right = pressed,
-
-            Key::D1 if pressed => {
-                self.current_action = Action::CreatingBall;
-                self.action_step = 0;
+        // Keys are text while typing into a label, only confirming and cancelling still work.
+        // Releases get through so nothing held from before stays held
+        if let Some(i) = self.editing_label {
+            match key {
+                Key::Backspace if pressed => {
+                    self.labels[i].text.pop();
+                }
+                Key::LShift | Key::RShift => self.shift_held = pressed,
+                Key::LCtrl | Key::RCtrl => self.ctrl_held = pressed,
+                Key::LAlt | Key::RAlt => self.alt_held = pressed,
+                _ if pressed => {
+                    let modifiers = self.modifiers();
+                    match self.bindings.pressed(Trigger::Key(key), &modifiers) {
+                        Some(Control::Confirm) => self.finish_editing_label(),
+                        Some(Control::Cancel) => self.cancel_action(),
+                        _ => (),
+                    }
+                }
+                _ => self.handle_trigger(Trigger::Key(key), false),
             }
 
-            Key::D2 if pressed => {
-                self.current_action = Action::CreatingCuboid;
-                self.action_step = 0;
-            }
+            return;
+        }
 
-            Key::D4 if pressed => {
-                self.current_action = Action::CreatingBallInSocket;
-                self.action_step = 0;
-            }
+        match key {
+            Key::LShift | Key::RShift => self.shift_held = pressed,
+            Key::LCtrl | Key::RCtrl => self.ctrl_held = pressed,
+            Key::LAlt | Key::RAlt => self.alt_held = pressed,
+            _ => self.handle_trigger(Trigger::Key(key), pressed),
+        }
+    }
 
-            Key::Space if pressed => {
-                self.paused = !self.paused;
-            }
+    fn handle_text(&mut self, text: &str) {
+        if let Some(i) = self.editing_label {
+            self.labels[i].text.extend(text.chars().filter(|c| !c.is_control()));
+        }
+    }
 
-            Key::W if pressed => self.zoom_in(),
-            Key::S if pressed => self.zoom_out(),
+    fn handle_gamepad_axis(&mut self, axis: GamepadAxis, position: f64) {
+        self.gamepad_axes[axis as usize] = position;
+    }
 
+    // Not rebindable, every gamepad gets the same layout
+    fn handle_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        match button {
+            GamepadButton::South => self.control(Control::UseTool, pressed),
+            GamepadButton::East => self.control(Control::Cancel, pressed),
+            GamepadButton::Start => self.control(Control::Pause, pressed),
             _ => (),
         }
     }
@@ -517,4 +2571,99 @@ impl State for Game {
     fn handle_resize(&mut self, width: u32, height: u32) {
         self.camera.set_size(width, height);
     }
+
+    fn status(&self) -> Option<Status> {
+        Some(Status {
+                 tool: self.tool(),
+                 action: self.action_name(),
+                 paused: self.paused,
+                 inspected: self.inspected
+                     .and_then(|id| self.find_body(id))
+                     .map(|body| Properties::from_body(&body.borrow())),
+             })
+    }
+
+    fn handle_ui_action(&mut self, action: UiAction) {
+        match action {
+            UiAction::SelectTool(tool) => {
+                self.set_action(match tool {
+                                    Tool::Grab => Action::None,
+                                    Tool::Ball => Action::CreatingBall,
+                                    Tool::Box => Action::CreatingCuboid,
+                                    Tool::Polygon => Action::DrawingPolygon,
+                                    Tool::BallInSocket => Action::CreatingBallInSocket,
+                                    Tool::FixedJoint => Action::CreatingFixedJoint,
+                                    Tool::Delete => Action::Erasing,
+                                    Tool::Inspect => Action::Inspecting,
+                                })
+            }
+            UiAction::TogglePause => self.paused = !self.paused,
+            // Single steps are only useful while paused
+            UiAction::Step => {
+                self.paused = true;
+                self.step_world();
+            }
+            UiAction::SetMotion(motion) => {
+                self.edit_inspected(|id, before| {
+                                        Command::SetMotion {
+                                            id: id,
+                                            before: before.motion,
+                                            after: motion,
+                                        }
+                                    })
+            }
+            UiAction::SetMaterial(material) => {
+                self.edit_inspected(|id, before| {
+                                        Command::SetMaterial {
+                                            id: id,
+                                            before: before.material,
+                                            after: material,
+                                        }
+                                    })
+            }
+            UiAction::FinishEdit => self.history.end_merge(),
+        }
+    }
+
+    fn take_loads(&mut self) -> Vec<Load> {
+        mem::replace(&mut self.loads, vec![])
+    }
+
+    fn handle_load(&mut self, load: Load) {
+        match load {
+            Load::Scene { scene } => {
+                self.load_scene(scene);
+                self.watch_scene_file();
+            }
+            Load::Reload { scene } => self.reload_scene(scene),
+            Load::Prefab { path, blueprint } => self.import_prefab(&path, blueprint),
+        }
+    }
+
+    fn take_transition(&mut self) -> Transition {
+        mem::replace(&mut self.transition, Transition::None)
+    }
+}
+
+fn set_motion(rb: &mut RigidBody<f64>, motion: &Motion) {
+    let translation = na::Vector2::new(motion.position[0], motion.position[1]);
+    rb.set_transformation(na::Isometry2::new(translation, motion.rotation));
+    rb.set_lin_vel(na::Vector2::new(motion.lin_vel[0], motion.lin_vel[1]));
+    rb.set_ang_vel(na::Vector1::new(motion.ang_vel));
+    rb.activate(1.0);
+}
+
+fn is_plane(body: &RigidBodyHandle<f64>) -> bool {
+    body.borrow().shape().as_ref().as_shape::<Plane2<f64>>().is_some()
+}
+
+// A label as the history describes it
+fn label_desc(label: &Label) -> LabelDesc<BodyId> {
+    let position = label.local_position();
+    LabelDesc {
+        text: label.text.clone(),
+        position: [position.x, position.y],
+        size: label.size,
+        body: label.body().and_then(body_id),
+    }
 }
@@ -1,20 +1,80 @@
+use blueprint::Blueprint;
+use gamepad::{GamepadAxis, GamepadButton};
 use graphics::Context;
 use opengl_graphics::GlGraphics;
 use piston::input::{Key, MouseButton};
+use raster::Canvas;
+use scene::Scene;
+use std::path::PathBuf;
+use toolbar::{Status, UiAction};
 
 mod game;
+mod pause;
+mod restore;
+mod stack;
 
 pub use self::game::Game;
+pub use self::pause::Pause;
+pub use self::restore::Restore;
+pub use self::stack::StateStack;
+
+// What a state wants done to the stack it's on
+pub enum Transition {
+    None,
+    Push(Box<State>),
+    Pop,
+    Replace(Box<State>),
+}
+
+// A file read by a state. It's handed to the app, which records it and sends it back through
+// `handle_load`, so replays get the same contents whatever is on disk by then
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Load {
+    // Opened with ctrl+o
+    Scene { scene: Scene },
+    // The opened scene changed on disk
+    Reload { scene: Scene },
+    // Picked from the prefab directory with ctrl+i
+    Prefab { path: PathBuf, blueprint: Blueprint },
+}
 
 pub trait State {
     fn update(&mut self, dt: f64);
     fn render(&self, c: &Context, g: &mut GlGraphics);
+    // Same as `render` without a GPU, used for screenshots
+    fn render_software(&self, _c: &Context, _canvas: &mut Canvas) {}
 
     fn handle_mouse_move(&mut self, _x: f64, _y: f64) {}
     fn handle_mouse_button(&mut self, _button: MouseButton, _pressed: bool) {}
     fn handle_mouse_scroll(&mut self, _x: f64, _y: f64) {}
 
     fn handle_key(&mut self, _key: Key, _pressed: bool) {}
+    fn handle_text(&mut self, _text: &str) {}
+
+    fn handle_gamepad_axis(&mut self, _axis: GamepadAxis, _position: f64) {}
+    fn handle_gamepad_button(&mut self, _button: GamepadButton, _pressed: bool) {}
 
     fn handle_resize(&mut self, _width: u32, _height: u32) {}
+
+    // The toolbar is hidden for states without a status
+    fn status(&self) -> Option<Status> {
+        None
+    }
+    fn handle_ui_action(&mut self, _action: UiAction) {}
+
+    // Files read since the last call
+    fn take_loads(&mut self) -> Vec<Load> {
+        Vec::new()
+    }
+    fn handle_load(&mut self, _load: Load) {}
+
+    // Whether the states below are drawn underneath this one
+    fn is_transparent(&self) -> bool {
+        false
+    }
+
+    // Called by the stack after every update and input, the request is cleared
+    fn take_transition(&mut self) -> Transition {
+        Transition::None
+    }
 }
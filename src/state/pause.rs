@@ -0,0 +1,85 @@
+use super::{State, Transition};
+use color;
+use gamepad::GamepadButton;
+use graphics::{self, Context, Graphics};
+use opengl_graphics::GlGraphics;
+use piston::input::{Key, MouseButton};
+use raster::Canvas;
+
+// Dims whatever is underneath until a key or a mouse or gamepad button is pressed
+pub struct Pause {
+    width: f64,
+    height: f64,
+    resumed: bool,
+}
+
+impl Pause {
+    pub fn new() -> Self {
+        Pause {
+            width: 0.0,
+            height: 0.0,
+            resumed: false,
+        }
+    }
+
+    fn draw<G: Graphics>(&self, c: &Context, g: &mut G) {
+        let mut dim = color::BLACK;
+        dim[3] = 0.5;
+        graphics::rectangle(dim, [0.0, 0.0, self.width, self.height], c.transform, g);
+
+        // Pause sign in the middle
+        let size = self.width.min(self.height) / 8.0;
+        let x = self.width / 2.0;
+        let y = self.height / 2.0 - size / 2.0;
+        graphics::rectangle(color::WHITE,
+                            [x - size / 2.0, y, size / 3.0, size],
+                            c.transform,
+                            g);
+        graphics::rectangle(color::WHITE,
+                            [x + size / 6.0, y, size / 3.0, size],
+                            c.transform,
+                            g);
+    }
+}
+
+impl State for Pause {
+    fn update(&mut self, _dt: f64) {}
+
+    fn render(&self, c: &Context, g: &mut GlGraphics) {
+        self.draw(c, g);
+    }
+
+    fn render_software(&self, c: &Context, canvas: &mut Canvas) {
+        self.draw(c, canvas);
+    }
+
+    fn handle_mouse_button(&mut self, _button: MouseButton, pressed: bool) {
+        self.resumed |= pressed;
+    }
+
+    fn handle_key(&mut self, _key: Key, pressed: bool) {
+        self.resumed |= pressed;
+    }
+
+    fn handle_gamepad_button(&mut self, _button: GamepadButton, pressed: bool) {
+        self.resumed |= pressed;
+    }
+
+    fn handle_resize(&mut self, width: u32, height: u32) {
+        self.width = width as f64;
+        self.height = height as f64;
+    }
+
+    fn is_transparent(&self) -> bool {
+        true
+    }
+
+    fn take_transition(&mut self) -> Transition {
+        if self.resumed {
+            self.resumed = false;
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+}
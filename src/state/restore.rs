@@ -0,0 +1,142 @@
+use super::{Game, State, Transition};
+use color;
+use gamepad::GamepadButton;
+use graphics::{self, Context, Graphics, Transformed};
+use graphics::character::CharacterCache;
+use opengl_graphics::{GlGraphics, GlyphCache};
+use piston::input::Key;
+use raster::{self, Canvas};
+use recovery::Recovery;
+use scene::Scene;
+use std::cell::RefCell;
+use std::rc::Rc;
+use view::FONT;
+
+const LINES: [&'static str; 2] = ["The last session didn't exit cleanly",
+                                  "Restore its autosave? Y / N"];
+const FONT_SIZE: u32 = 20;
+const LINE_HEIGHT: f64 = 32.0;
+
+// Asks whether to bring back what a crashed session autosaved, with the game shown underneath.
+// The game replaces this once answered, nothing is autosaved over the crashed scene before then
+pub struct Restore {
+    game: Option<Game>,
+    scene: Option<Scene>,
+    recovery: Rc<Recovery>,
+    width: f64,
+    height: f64,
+    answer: Option<bool>,
+    glyphs: RefCell<Option<GlyphCache<'static>>>,
+    software_glyphs: RefCell<Option<raster::GlyphCache>>,
+}
+
+impl Restore {
+    pub fn new(game: Game, scene: Scene, recovery: Rc<Recovery>) -> Self {
+        Restore {
+            game: Some(game),
+            scene: Some(scene),
+            recovery: recovery,
+            width: 0.0,
+            height: 0.0,
+            answer: None,
+            glyphs: RefCell::new(None),
+            software_glyphs: RefCell::new(None),
+        }
+    }
+
+    fn draw<G, C>(&self, c: &Context, g: &mut G, glyphs: &mut C)
+        where G: Graphics,
+              C: CharacterCache<Texture = G::Texture>
+    {
+        let mut dim = color::BLACK;
+        dim[3] = 0.5;
+        graphics::rectangle(dim, [0.0, 0.0, self.width, self.height], c.transform, g);
+
+        for (i, line) in LINES.iter().enumerate() {
+            let x = (self.width - glyphs.width(FONT_SIZE, line)) / 2.0;
+            let y = self.height / 2.0 + i as f64 * LINE_HEIGHT;
+            graphics::Text::new_color(color::WHITE, FONT_SIZE)
+                .draw(line, glyphs, &c.draw_state, c.trans(x, y).transform, g);
+        }
+    }
+}
+
+impl State for Restore {
+    fn update(&mut self, _dt: f64) {}
+
+    fn render(&self, c: &Context, g: &mut GlGraphics) {
+        if let Some(ref game) = self.game {
+            game.render(c, g);
+        }
+
+        let mut glyphs = self.glyphs.borrow_mut();
+        if glyphs.is_none() {
+            *glyphs = Some(GlyphCache::from_bytes(FONT).expect("invalid embedded font"));
+        }
+        self.draw(c, g, glyphs.as_mut().unwrap());
+    }
+
+    fn render_software(&self, c: &Context, canvas: &mut Canvas) {
+        if let Some(ref game) = self.game {
+            game.render_software(c, canvas);
+        }
+
+        let mut glyphs = self.software_glyphs.borrow_mut();
+        if glyphs.is_none() {
+            *glyphs = Some(raster::GlyphCache::from_bytes(FONT).expect("invalid embedded font"));
+        }
+        self.draw(c, canvas, glyphs.as_mut().unwrap());
+    }
+
+    fn handle_key(&mut self, key: Key, pressed: bool) {
+        if !pressed {
+            return;
+        }
+
+        match key {
+            Key::Y | Key::Return => self.answer = Some(true),
+            Key::N | Key::Escape => self.answer = Some(false),
+            _ => (),
+        }
+    }
+
+    // Same buttons as using a tool and cancelling in the game
+    fn handle_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        if !pressed {
+            return;
+        }
+
+        match button {
+            GamepadButton::South => self.answer = Some(true),
+            GamepadButton::East => self.answer = Some(false),
+            _ => (),
+        }
+    }
+
+    fn handle_resize(&mut self, width: u32, height: u32) {
+        self.width = width as f64;
+        self.height = height as f64;
+        if let Some(ref mut game) = self.game {
+            game.handle_resize(width, height);
+        }
+    }
+
+    fn take_transition(&mut self) -> Transition {
+        let restore = match self.answer.take() {
+            Some(restore) => restore,
+            None => return Transition::None,
+        };
+        let mut game = match self.game.take() {
+            Some(game) => game,
+            None => return Transition::Pop,
+        };
+
+        if restore {
+            if let Some(scene) = self.scene.take() {
+                game.load_scene(scene);
+            }
+        }
+        self.recovery.start(&mut game);
+        Transition::Replace(Box::new(game))
+    }
+}
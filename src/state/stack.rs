@@ -0,0 +1,193 @@
+use super::{Load, State, Transition};
+use gamepad::{GamepadAxis, GamepadButton};
+use graphics::Context;
+use opengl_graphics::GlGraphics;
+use piston::input::{Key, MouseButton};
+use raster::Canvas;
+use toolbar::{Status, UiAction};
+
+// Screens on top of each other, only the top one is updated and gets input
+pub struct StateStack {
+    states: Vec<Box<State>>,
+    // Window size, given to states as they're pushed
+    width: u32,
+    height: u32,
+}
+
+impl StateStack {
+    pub fn new(initial: Box<State>, width: u32, height: u32) -> Self {
+        StateStack {
+            states: vec![initial],
+            width: width,
+            height: height,
+        }
+    }
+
+    // Nothing left to show once the last state pops itself
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn push(&mut self, mut state: Box<State>) {
+        state.handle_resize(self.width, self.height);
+        self.states.push(state);
+    }
+
+    pub fn pop(&mut self) -> Option<Box<State>> {
+        self.states.pop()
+    }
+
+    pub fn replace(&mut self, state: Box<State>) {
+        self.states.pop();
+        self.push(state);
+    }
+
+    // Does whatever the top state asked for while it was handling something
+    fn apply_transition(&mut self) {
+        let transition = match self.states.last_mut() {
+            Some(state) => state.take_transition(),
+            None => return,
+        };
+
+        match transition {
+            Transition::None => {}
+            Transition::Push(state) => self.push(state),
+            Transition::Pop => {
+                self.pop();
+            }
+            Transition::Replace(state) => self.replace(state),
+        }
+    }
+
+    // States are drawn from the first opaque one from the top
+    fn visible(&self) -> &[Box<State>] {
+        let first = self.states.iter().rposition(|s| !s.is_transparent()).unwrap_or(0);
+        &self.states[first..]
+    }
+}
+
+impl State for StateStack {
+    fn update(&mut self, dt: f64) {
+        if let Some(state) = self.states.last_mut() {
+            state.update(dt);
+        }
+        self.apply_transition();
+    }
+
+    fn render(&self, c: &Context, g: &mut GlGraphics) {
+        for state in self.visible() {
+            state.render(c, g);
+        }
+    }
+
+    fn render_software(&self, c: &Context, canvas: &mut Canvas) {
+        for state in self.visible() {
+            state.render_software(c, canvas);
+        }
+    }
+
+    fn handle_mouse_move(&mut self, x: f64, y: f64) {
+        if let Some(state) = self.states.last_mut() {
+            state.handle_mouse_move(x, y);
+        }
+        self.apply_transition();
+    }
+
+    fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        // Releases go everywhere, so nothing underneath thinks a button is still held
+        if pressed {
+            if let Some(state) = self.states.last_mut() {
+                state.handle_mouse_button(button, pressed);
+            }
+        } else {
+            for state in &mut self.states {
+                state.handle_mouse_button(button, pressed);
+            }
+        }
+        self.apply_transition();
+    }
+
+    fn handle_mouse_scroll(&mut self, x: f64, y: f64) {
+        if let Some(state) = self.states.last_mut() {
+            state.handle_mouse_scroll(x, y);
+        }
+        self.apply_transition();
+    }
+
+    fn handle_key(&mut self, key: Key, pressed: bool) {
+        if pressed {
+            if let Some(state) = self.states.last_mut() {
+                state.handle_key(key, pressed);
+            }
+        } else {
+            for state in &mut self.states {
+                state.handle_key(key, pressed);
+            }
+        }
+        self.apply_transition();
+    }
+
+    fn handle_text(&mut self, text: &str) {
+        if let Some(state) = self.states.last_mut() {
+            state.handle_text(text);
+        }
+        self.apply_transition();
+    }
+
+    // Stick positions go everywhere like releases, states only act on them while updated
+    fn handle_gamepad_axis(&mut self, axis: GamepadAxis, position: f64) {
+        for state in &mut self.states {
+            state.handle_gamepad_axis(axis, position);
+        }
+        self.apply_transition();
+    }
+
+    fn handle_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        if pressed {
+            if let Some(state) = self.states.last_mut() {
+                state.handle_gamepad_button(button, pressed);
+            }
+        } else {
+            for state in &mut self.states {
+                state.handle_gamepad_button(button, pressed);
+            }
+        }
+        self.apply_transition();
+    }
+
+    fn status(&self) -> Option<Status> {
+        self.states.last().and_then(|state| state.status())
+    }
+
+    fn handle_ui_action(&mut self, action: UiAction) {
+        if let Some(state) = self.states.last_mut() {
+            state.handle_ui_action(action);
+        }
+        self.apply_transition();
+    }
+
+    fn take_loads(&mut self) -> Vec<Load> {
+        let mut loads = vec![];
+        for state in &mut self.states {
+            loads.extend(state.take_loads());
+        }
+        loads
+    }
+
+    // Goes back to every state, only the one that read the file acts on it
+    fn handle_load(&mut self, load: Load) {
+        for state in &mut self.states {
+            state.handle_load(load.clone());
+        }
+        self.apply_transition();
+    }
+
+    // Every state is kept the right size, not only the top one
+    fn handle_resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        for state in &mut self.states {
+            state.handle_resize(width, height);
+        }
+    }
+}
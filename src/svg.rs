@@ -0,0 +1,176 @@
+use graphics::Colored;
+use graphics::types::Color;
+use na;
+use ncollide::shape::{Ball2, Compound2, ConvexHull2, Cuboid2, Plane2, Shape2};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Same as the borders drawn by `view`, in world units
+const BORDER_WIDTH: f64 = 0.2;
+
+// An SVG document in world units, y points down like on screen
+pub struct Svg {
+    mins: na::Point2<f64>,
+    maxs: na::Point2<f64>,
+    elements: String,
+}
+
+impl Svg {
+    pub fn new(mins: na::Point2<f64>, maxs: na::Point2<f64>, background: Color) -> Self {
+        let size = maxs - mins;
+        let mut svg = Svg {
+            mins: mins,
+            maxs: maxs,
+            elements: String::new(),
+        };
+
+        svg.elements.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                        fill=\"{}\"/>\n",
+                                       mins.x,
+                                       mins.y,
+                                       size.x,
+                                       size.y,
+                                       rgb(background)));
+        svg
+    }
+
+    // Draws `shape` the way `view::draw_shape` does, planes included
+    pub fn shape(&mut self,
+                 shape: &Shape2<f64>,
+                 position: &na::Isometry2<f64>,
+                 margin: f64,
+                 color: Color) {
+        if let Some(s) = shape.as_shape::<Plane2<f64>>() {
+            self.plane(position.translation.vector, position.rotation * *s.normal(), color);
+            return;
+        }
+
+        self.begin_group(position.translation.vector.x,
+                         position.translation.vector.y,
+                         position.rotation.angle());
+        self.local_shape(shape, margin, color);
+        self.end_group();
+    }
+
+    pub fn line(&mut self,
+                a: &na::Point2<f64>,
+                b: &na::Point2<f64>,
+                color: Color,
+                width: f64) {
+        self.elements.push_str(&format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" \
+                                         stroke=\"{}\" stroke-opacity=\"{}\" \
+                                         stroke-width=\"{}\" stroke-linecap=\"round\"/>\n",
+                                        a.x,
+                                        a.y,
+                                        b.x,
+                                        b.y,
+                                        rgb(color),
+                                        color[3],
+                                        width));
+    }
+
+    // `position` is the start of the baseline, `size` the height of the text
+    pub fn text(&mut self, position: &na::Point2<f64>, size: f64, text: &str, color: Color) {
+        self.elements.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" \
+                                         font-family=\"DejaVu Sans\" fill=\"{}\">{}</text>\n",
+                                        position.x,
+                                        position.y,
+                                        size,
+                                        rgb(color),
+                                        escape(text)));
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let size = self.maxs - self.mins;
+        let mut file = File::create(path)?;
+        write!(file,
+               "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n{}</svg>\n",
+               self.mins.x,
+               self.mins.y,
+               size.x,
+               size.y,
+               self.elements)
+    }
+
+    fn local_shape(&mut self, shape: &Shape2<f64>, margin: f64, color: Color) {
+        if let Some(s) = shape.as_shape::<Ball2<f64>>() {
+            self.elements.push_str(&format!("<circle r=\"{}\" {}/>\n",
+                                            s.radius() + margin,
+                                            style(color)));
+        } else if let Some(s) = shape.as_shape::<Cuboid2<f64>>() {
+            let width = s.half_extents().x + margin;
+            let height = s.half_extents().y + margin;
+            self.elements.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" \
+                                             height=\"{}\" {}/>\n",
+                                            -width,
+                                            -height,
+                                            width * 2.0,
+                                            height * 2.0,
+                                            style(color)));
+        } else if let Some(s) = shape.as_shape::<ConvexHull2<f64>>() {
+            let points: Vec<String> =
+                s.points().iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+            self.elements.push_str(&format!("<polygon points=\"{}\" {}/>\n",
+                                            points.join(" "),
+                                            style(color)));
+        } else if let Some(s) = shape.as_shape::<Compound2<f64>>() {
+            for &(ref isometry, ref part) in s.shapes() {
+                self.begin_group(isometry.translation.vector.x,
+                                 isometry.translation.vector.y,
+                                 isometry.rotation.angle());
+                self.local_shape(part.as_ref(), margin, color);
+                self.end_group();
+            }
+        }
+    }
+
+    // Planes are infinite, the part of the half space inside the document is filled
+    fn plane(&mut self, point: na::Vector2<f64>, normal: na::Vector2<f64>, color: Color) {
+        let center = na::center(&self.mins, &self.maxs).coords;
+        let extent = na::norm(&(self.maxs - self.mins)) + na::norm(&(center - point));
+        let along = na::Vector2::new(-normal.y, normal.x) * extent;
+        let inside = -normal * extent;
+
+        let corners = [point + along,
+                       point - along,
+                       point - along + inside,
+                       point + along + inside];
+        let points: Vec<String> = corners.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+        self.elements.push_str(&format!("<polygon points=\"{}\" {}/>\n",
+                                        points.join(" "),
+                                        style(color)));
+    }
+
+    fn begin_group(&mut self, x: f64, y: f64, rotation: f64) {
+        self.elements.push_str(&format!("<g transform=\"translate({} {}) rotate({})\">\n",
+                                        x,
+                                        y,
+                                        rotation.to_degrees()));
+    }
+
+    fn end_group(&mut self) {
+        self.elements.push_str("</g>\n");
+    }
+}
+
+// Filled with `color` and outlined with a darker shade, like `view::draw_ball`
+fn style(color: Color) -> String {
+    format!("fill=\"{}\" fill-opacity=\"{}\" stroke=\"{}\" stroke-width=\"{}\"",
+            rgb(color),
+            color[3],
+            rgb(color.shade(0.5)),
+            BORDER_WIDTH)
+}
+
+fn rgb(color: Color) -> String {
+    let channel = |c: f32| (na::clamp(c, 0.0, 1.0) * 255.0).round() as u8;
+    format!("rgb({},{},{})",
+            channel(color[0]),
+            channel(color[1]),
+            channel(color[2]))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
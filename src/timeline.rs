@@ -0,0 +1,77 @@
+use blueprint::{BodyDesc, BodyId, JointDesc, JointId};
+use history::History;
+use scene::LabelDesc;
+use std::collections::VecDeque;
+
+// The world at some step, bodies and joints keep the ids they had
+#[derive(Clone)]
+pub struct Snapshot {
+    pub step: u64,
+    pub bodies: Vec<(BodyId, BodyDesc)>,
+    pub joints: Vec<(JointId, JointDesc<BodyId>)>,
+    pub labels: Vec<LabelDesc<BodyId>>,
+    // What could be undone at that point, later commands may refer to bodies that didn't exist
+    pub history: History,
+}
+
+// Bounded buffer of snapshots, the oldest are dropped first
+pub struct Timeline {
+    snapshots: VecDeque<Snapshot>,
+    limit: usize,
+    // Snapshot the world was rewound to, `None` while live
+    cursor: Option<usize>,
+}
+
+impl Timeline {
+    pub fn new(limit: usize) -> Self {
+        Timeline {
+            snapshots: VecDeque::new(),
+            limit: limit,
+            cursor: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() >= self.limit {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn seek(&mut self, index: usize) -> Option<&Snapshot> {
+        if index < self.snapshots.len() {
+            self.cursor = Some(index);
+        }
+
+        self.snapshots.get(index)
+    }
+
+    // Makes the snapshot the world was rewound to the latest one, goes live again
+    pub fn branch(&mut self) {
+        if let Some(cursor) = self.cursor.take() {
+            self.snapshots.truncate(cursor + 1);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.cursor = None;
+    }
+}
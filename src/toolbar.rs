@@ -0,0 +1,280 @@
+use color;
+use conrod::{self, widget, Colorable, Labelable, Positionable, Sizeable, Widget};
+use conrod::backend::piston::{draw, event};
+use graphics::Context;
+use graphics::types::Color;
+use history::{Material, Motion, Properties};
+use image::{ImageBuffer, Rgba};
+use inspector;
+use opengl_graphics::{GlGraphics, Texture, TextureSettings};
+use piston::input::{Button, Input, Motion};
+use view::FONT;
+
+// Tools the toolbar has buttons for
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Tool {
+    Grab,
+    Ball,
+    Box,
+    Polygon,
+    BallInSocket,
+    FixedJoint,
+    Delete,
+    Inspect,
+}
+
+const TOOLS: [Tool; 8] = [Tool::Grab,
+                          Tool::Ball,
+                          Tool::Box,
+                          Tool::Polygon,
+                          Tool::BallInSocket,
+                          Tool::FixedJoint,
+                          Tool::Delete,
+                          Tool::Inspect];
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match *self {
+            Tool::Grab => "Grab",
+            Tool::Ball => "Ball",
+            Tool::Box => "Box",
+            Tool::Polygon => "Polygon",
+            Tool::BallInSocket => "Hinge",
+            Tool::FixedJoint => "Weld",
+            Tool::Delete => "Delete",
+            Tool::Inspect => "Inspect",
+        }
+    }
+}
+
+// What a click on the toolbar asks of the current state
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub enum UiAction {
+    SelectTool(Tool),
+    TogglePause,
+    Step,
+    // Change the body shown in the inspector, only the half that was edited is sent so the other
+    // isn't set back to what it was when the panel was drawn
+    SetMotion(Motion),
+    SetMaterial(Material),
+    // The mouse was let go after changing properties, the next change is a separate edit
+    FinishEdit,
+}
+
+// What the toolbar shows for the current state
+pub struct Status {
+    // Highlighted button, `None` when doing something without one
+    pub tool: Option<Tool>,
+    pub action: &'static str,
+    pub paused: bool,
+    // Body shown in the inspector panel
+    pub inspected: Option<Properties>,
+}
+
+widget_ids! {
+    struct Ids {
+        bar,
+        tools[],
+        play,
+        step,
+        action,
+    }
+}
+
+const BAR_HEIGHT: f64 = 32.0;
+const BUTTON_WIDTH: f64 = 56.0;
+const BUTTON_HEIGHT: f64 = 24.0;
+const MARGIN: f64 = 4.0;
+const FONT_SIZE: u32 = 12;
+
+// Big enough for every glyph the toolbar uses
+const GLYPH_CACHE_SIZE: u32 = 512;
+
+// Buttons along the top of the window and the inspector panel, drawn over whatever state is on
+// top
+pub struct Toolbar {
+    ui: conrod::Ui,
+    ids: Ids,
+    inspector_ids: inspector::Ids,
+    glyph_cache: conrod::text::GlyphCache,
+    // opengl_graphics can only upload whole images, so the cache texture is kept here too
+    glyph_image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    glyph_texture: Texture,
+    image_map: conrod::image::Map<Texture>,
+    // Set from a change in the inspector until the mouse is let go
+    editing: bool,
+}
+
+impl Toolbar {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut ui = conrod::UiBuilder::new([width as f64, height as f64]).build();
+        let font = conrod::text::FontCollection::from_bytes(FONT).into_font();
+        ui.fonts.insert(font.expect("invalid embedded font"));
+
+        let mut ids = Ids::new(ui.widget_id_generator());
+        ids.tools.resize(TOOLS.len(), &mut ui.widget_id_generator());
+        let mut inspector_ids = inspector::Ids::new(ui.widget_id_generator());
+        inspector_ids.resize(&mut ui.widget_id_generator());
+
+        let glyph_image = ImageBuffer::from_pixel(GLYPH_CACHE_SIZE,
+                                                  GLYPH_CACHE_SIZE,
+                                                  Rgba([255, 255, 255, 0]));
+        let glyph_texture = Texture::from_image(&glyph_image, &TextureSettings::new());
+
+        Toolbar {
+            ui: ui,
+            ids: ids,
+            inspector_ids: inspector_ids,
+            glyph_cache: conrod::text::GlyphCache::new(GLYPH_CACHE_SIZE,
+                                                       GLYPH_CACHE_SIZE,
+                                                       0.1,
+                                                       0.1),
+            glyph_image: glyph_image,
+            glyph_texture: glyph_texture,
+            image_map: conrod::image::Map::new(),
+            editing: false,
+        }
+    }
+
+    // Returns whether the toolbar used the input, the states shouldn't see it then
+    pub fn handle_input(&mut self, input: &Input) -> bool {
+        if let Some(e) = event::convert(input.clone(), self.ui.win_w, self.ui.win_h) {
+            self.ui.handle_event(e);
+        }
+
+        // The window counts as a widget, only the ones on it take input away from the states
+        let window = self.ui.window;
+        let current = &self.ui.global_input().current;
+        let is_widget = |id: Option<widget::Id>| id.map_or(false, |id| id != window);
+        match *input {
+            Input::Press(Button::Mouse(_)) |
+            Input::Move(Motion::MouseScroll(..)) => is_widget(current.widget_under_mouse),
+            Input::Press(Button::Keyboard(_)) |
+            Input::Text(_) => is_widget(current.widget_capturing_keyboard),
+            // Releases, cursor moves and resizes always get through, so nothing below gets stuck
+            _ => false,
+        }
+    }
+
+    // Lays out the widgets for `status`, nothing is shown without one
+    pub fn update(&mut self, status: Option<Status>) -> Vec<UiAction> {
+        let mut actions = vec![];
+        let ui = &mut self.ui.set_widgets();
+        let status = match status {
+            Some(status) => status,
+            None => return actions,
+        };
+
+        let mut background = color::BLACK;
+        background[3] = 0.6;
+        widget::Canvas::new()
+            .w_h(ui.win_w, BAR_HEIGHT)
+            .top_left_of(ui.window)
+            .color(ui_color(background))
+            .set(self.ids.bar, ui);
+
+        for (i, &tool) in TOOLS.iter().enumerate() {
+            let button_color = if status.tool == Some(tool) {
+                color::SELECTION
+            } else {
+                color::WHITE
+            };
+
+            let button = widget::Button::new()
+                .label(tool.name())
+                .label_font_size(FONT_SIZE)
+                .w_h(BUTTON_WIDTH, BUTTON_HEIGHT)
+                .color(ui_color(button_color));
+            let button = if i == 0 {
+                button.mid_left_with_margin_on(self.ids.bar, MARGIN)
+            } else {
+                button.right_from(self.ids.tools[i - 1], MARGIN)
+            };
+
+            for _click in button.set(self.ids.tools[i], ui) {
+                actions.push(UiAction::SelectTool(tool));
+            }
+        }
+
+        let play = if status.paused { "Play" } else { "Pause" };
+        for _click in widget::Button::new()
+                .label(play)
+                .label_font_size(FONT_SIZE)
+                .w_h(BUTTON_WIDTH, BUTTON_HEIGHT)
+                .color(ui_color(color::WHITE))
+                .right_from(self.ids.tools[TOOLS.len() - 1], MARGIN * 4.0)
+                .set(self.ids.play, ui) {
+            actions.push(UiAction::TogglePause);
+        }
+
+        for _click in widget::Button::new()
+                .label("Step")
+                .label_font_size(FONT_SIZE)
+                .w_h(BUTTON_WIDTH, BUTTON_HEIGHT)
+                .color(ui_color(color::WHITE))
+                .right_from(self.ids.play, MARGIN)
+                .set(self.ids.step, ui) {
+            actions.push(UiAction::Step);
+        }
+
+        let action = format!("tool: {}", status.action);
+        widget::Text::new(&action)
+            .font_size(FONT_SIZE)
+            .color(ui_color(color::WHITE))
+            .right_from(self.ids.step, MARGIN * 4.0)
+            .set(self.ids.action, ui);
+
+        if let Some(ref properties) = status.inspected {
+            if let Some(edited) = inspector::set(ui, &self.inspector_ids, BAR_HEIGHT, properties) {
+                if edited.motion != properties.motion {
+                    actions.push(UiAction::SetMotion(edited.motion));
+                }
+                if edited.material != properties.material {
+                    actions.push(UiAction::SetMaterial(edited.material));
+                }
+                self.editing = true;
+            }
+        }
+
+        if self.editing && ui.global_input().current.mouse.buttons.left().is_up() {
+            actions.push(UiAction::FinishEdit);
+            self.editing = false;
+        }
+
+        actions
+    }
+
+    pub fn draw(&mut self, c: Context, g: &mut GlGraphics) {
+        let glyph_image = &mut self.glyph_image;
+        let cache_queued_glyphs = |_: &mut GlGraphics,
+                                   texture: &mut Texture,
+                                   rect: conrod::text::rt::Rect<u32>,
+                                   data: &[u8]| {
+            // White with the coverage in alpha, like the text textures opengl_graphics makes
+            let width = rect.width();
+            for (i, &coverage) in data.iter().enumerate() {
+                let x = rect.min.x + i as u32 % width;
+                let y = rect.min.y + i as u32 / width;
+                glyph_image.put_pixel(x, y, Rgba([255, 255, 255, coverage]));
+            }
+            texture.update(glyph_image);
+        };
+
+        fn texture_from_image<T>(image: &T) -> &T {
+            image
+        }
+
+        draw::primitives(self.ui.draw(),
+                         c,
+                         g,
+                         &mut self.glyph_texture,
+                         &mut self.glyph_cache,
+                         &self.image_map,
+                         cache_queued_glyphs,
+                         texture_from_image);
+    }
+}
+
+fn ui_color(c: Color) -> conrod::Color {
+    conrod::color::rgba(c[0], c[1], c[2], c[3])
+}
@@ -1,9 +1,102 @@
-use graphics::{self, Colored, Context};
+use blueprint::ShapeDesc;
+use graphics::{self, Colored, Context, Graphics, Transformed};
 use graphics::types::Color;
-use opengl_graphics::GlGraphics;
+use ncollide::shape::{Ball2, Compound2, ConvexHull2, Cuboid2, Shape2};
+
+// Built into the binary so it runs from anywhere
+pub const FONT: &'static [u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+// Draws any of the supported shapes, `c` is expected to be in the body's local space
+pub fn draw_shape<G: Graphics>(shape: &Shape2<f64>,
+                               margin: f64,
+                               color: Color,
+                               c: &Context,
+                               g: &mut G) {
+    if let Some(s) = shape.as_shape::<Ball2<f64>>() {
+        draw_ball(s.radius() + margin, color, c, g);
+    } else if let Some(s) = shape.as_shape::<Cuboid2<f64>>() {
+        let width = s.half_extents().x + margin;
+        let height = s.half_extents().y + margin;
+        draw_cuboid(width, height, color, c, g);
+    } else if let Some(s) = shape.as_shape::<ConvexHull2<f64>>() {
+        draw_polygon(&polygon_points(s), color, c, g);
+    } else if let Some(s) = shape.as_shape::<Compound2<f64>>() {
+        for &(ref isometry, ref part) in s.shapes() {
+            let c = part_context(isometry.translation.vector.x,
+                                 isometry.translation.vector.y,
+                                 isometry.rotation.angle(),
+                                 c);
+            draw_shape(part.as_ref(), margin, color, &c, g);
+        }
+    }
+}
+
+pub fn draw_shape_desc<G: Graphics>(shape: &ShapeDesc, color: Color, c: &Context, g: &mut G) {
+    match *shape {
+        ShapeDesc::Ball { radius } => draw_ball(radius, color, c, g),
+        ShapeDesc::Cuboid { half_extents } => {
+            draw_cuboid(half_extents[0], half_extents[1], color, c, g)
+        }
+        ShapeDesc::Polygon { ref points } => draw_polygon(points, color, c, g),
+        ShapeDesc::Compound { ref parts } => {
+            for part in parts {
+                let c = part_context(part.position[0], part.position[1], part.rotation.angle(), c);
+                draw_shape_desc(&part.shape, color, &c, g);
+            }
+        }
+        // Planes are infinite and there is nothing sensible to preview
+        ShapeDesc::Plane { .. } => {}
+    }
+}
+
+pub fn draw_shape_outline<G: Graphics>(shape: &Shape2<f64>,
+                                       margin: f64,
+                                       color: Color,
+                                       c: &Context,
+                                       g: &mut G) {
+    if let Some(s) = shape.as_shape::<Ball2<f64>>() {
+        let radius = s.radius() + margin;
+        let dradius = radius * 2.0;
+
+        graphics::Ellipse::new_border(color, 0.1)
+            .resolution(16)
+            .draw([-radius, -radius, dradius, dradius],
+                  &c.draw_state,
+                  c.transform,
+                  g);
+    } else if let Some(s) = shape.as_shape::<Cuboid2<f64>>() {
+        let width = s.half_extents().x + margin;
+        let height = s.half_extents().y + margin;
+
+        graphics::Rectangle::new_border(color, 0.1)
+            .draw([-width, -height, width * 2.0, height * 2.0],
+                  &c.draw_state,
+                  c.transform,
+                  g);
+    } else if let Some(s) = shape.as_shape::<ConvexHull2<f64>>() {
+        draw_polygon_outline(&polygon_points(s), color, c, g);
+    } else if let Some(s) = shape.as_shape::<Compound2<f64>>() {
+        for &(ref isometry, ref part) in s.shapes() {
+            let c = part_context(isometry.translation.vector.x,
+                                 isometry.translation.vector.y,
+                                 isometry.rotation.angle(),
+                                 c);
+            draw_shape_outline(part.as_ref(), margin, color, &c, g);
+        }
+    }
+}
+
+// Moves `c` from a compound's space to one of its parts
+fn part_context(x: f64, y: f64, rotation: f64, c: &Context) -> Context {
+    c.trans(x, y).rot_rad(rotation)
+}
+
+fn polygon_points(polygon: &ConvexHull2<f64>) -> Vec<[f64; 2]> {
+    polygon.points().iter().map(|p| [p.x, p.y]).collect()
+}
 
 // TODO: Use `Matrix2d` instead of `Context`?
-pub fn draw_ball(radius: f64, color: Color, c: &Context, g: &mut GlGraphics) {
+pub fn draw_ball<G: Graphics>(radius: f64, color: Color, c: &Context, g: &mut G) {
     let dradius = radius * 2.0;
 
     graphics::Ellipse::new(color)
@@ -19,7 +112,7 @@ pub fn draw_ball(radius: f64, color: Color, c: &Context, g: &mut GlGraphics) {
 }
 
 // TODO: Use `Matrix2d` instead of `Context`?
-pub fn draw_cuboid(width: f64, height: f64, color: Color, c: &Context, g: &mut GlGraphics) {
+pub fn draw_cuboid<G: Graphics>(width: f64, height: f64, color: Color, c: &Context, g: &mut G) {
     let dwidth = width * 2.0;
     let dheight = height * 2.0;
 
@@ -33,3 +126,21 @@ pub fn draw_cuboid(width: f64, height: f64, color: Color, c: &Context, g: &mut G
               c.transform,
               g);
 }
+
+// TODO: Use `Matrix2d` instead of `Context`?
+pub fn draw_polygon<G: Graphics>(points: &[[f64; 2]], color: Color, c: &Context, g: &mut G) {
+    graphics::Polygon::new(color).draw(points, &c.draw_state, c.transform, g);
+    draw_polygon_outline(points, color.shade(0.5), c, g);
+}
+
+pub fn draw_polygon_outline<G: Graphics>(points: &[[f64; 2]],
+                                         color: Color,
+                                         c: &Context,
+                                         g: &mut G) {
+    let border = graphics::Line::new(color, 0.1);
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        border.draw([a[0], a[1], b[0], b[1]], &c.draw_state, c.transform, g);
+    }
+}